@@ -0,0 +1,106 @@
+use std::borrow::Cow;
+use std::io::SeekFrom;
+use std::ops::Deref;
+use std::sync::Arc;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt};
+
+use crate::decode_payload;
+use crate::chunks::Chunk;
+use crate::container::{ChunkData, RawChunk, Slot};
+use crate::error::ChunkLoadError;
+use crate::external::ExternalChunkResolver;
+use crate::metadata::RegionHeader;
+
+/// The async counterpart of [`crate::RegionFileReader`].
+/// Only reading from the underlying reader is asynchronous.
+/// Decompression and parsing either happen inline ([`AsyncRegionFileReader::get_chunk`])
+/// or on tokio's blocking thread pool ([`AsyncRegionFileReader::get_chunk_blocking`]).
+pub struct AsyncRegionFileReader<R: AsyncRead + AsyncSeek + Unpin> {
+    reader: R,
+    header: RegionHeader,
+    external: Option<Arc<dyn ExternalChunkResolver>>
+}
+impl<R: AsyncRead + AsyncSeek + Unpin> AsyncRegionFileReader<R> {
+    pub async fn create(mut reader: R) -> std::io::Result<Self> {
+        reader.seek(SeekFrom::Start(0)).await?;
+        Ok(AsyncRegionFileReader {
+            header: RegionHeader::read_async(&mut reader).await?,
+            reader,
+            external: None
+        })
+    }
+
+    /// Sets the resolver used to load chunks that are stored outside of the region file.
+    /// Note that resolvers are synchronous, so resolving external chunks blocks
+    /// unless the chunk is loaded using [`AsyncRegionFileReader::get_chunk_blocking`].
+    pub fn with_external_resolver(mut self, resolver: impl ExternalChunkResolver + 'static) -> Self {
+        self.external = Some(Arc::new(resolver));
+        self
+    }
+
+    /// Reads all sectors occupied by the chunk, without decoding them.
+    pub async fn get_payload(&mut self, chunk_x: u8, chunk_z: u8) -> Result<Vec<u8>, ChunkLoadError> {
+        let (seek, size) = self.header.get_location(chunk_x, chunk_z)?.to_offset_form();
+        if size == 0 {
+            return Err(ChunkLoadError::ChunkDoesNotExist)
+        }
+        self.reader.seek(SeekFrom::Start(seek)).await?;
+        let mut buf = vec![0u8; size];
+        self.reader.read_exact(&mut buf).await?;
+        Ok(buf)
+    }
+
+    /// Reads the sectors of the slot without decoding them.
+    pub async fn read_slot(&mut self, chunk_x: u8, chunk_z: u8) -> Result<Slot<'_>, ChunkLoadError> {
+        let payload = self.get_payload(chunk_x, chunk_z).await?;
+        Ok(Slot::new(Cow::Owned(payload), self.external.as_deref(), chunk_x, chunk_z))
+    }
+
+    /// Loads the chunk, decoding it on the current task.
+    pub async fn get_chunk(&mut self, chunk_x: u8, chunk_z: u8) -> Result<Chunk, ChunkLoadError> {
+        self.get_chunk_as(chunk_x, chunk_z).await
+    }
+
+    /// Decodes the data on the current task, see [`Slot::decode`].
+    pub async fn get_chunk_as<T: ChunkData>(&mut self, chunk_x: u8, chunk_z: u8) -> Result<T, ChunkLoadError> {
+        self.read_slot(chunk_x, chunk_z).await?.decode()
+    }
+
+    /// See [`Slot::decompress`].
+    pub async fn get_chunk_bytes(&mut self, chunk_x: u8, chunk_z: u8) -> Result<Vec<u8>, ChunkLoadError> {
+        self.read_slot(chunk_x, chunk_z).await?.decompress().map(Cow::into_owned)
+    }
+
+    /// See [`Slot::into_raw`].
+    pub async fn get_raw_chunk(&mut self, chunk_x: u8, chunk_z: u8) -> Result<RawChunk, ChunkLoadError> {
+        self.read_slot(chunk_x, chunk_z).await?.into_raw()
+    }
+
+    /// Loads the chunk, decoding it on tokio's blocking thread pool.
+    /// This requires a tokio runtime.
+    pub async fn get_chunk_blocking(&mut self, chunk_x: u8, chunk_z: u8) -> Result<Chunk, ChunkLoadError> {
+        self.get_chunk_blocking_as(chunk_x, chunk_z).await
+    }
+
+    /// Loads the data as any type stored in Anvil containers, decoding it on tokio's blocking thread pool.
+    /// This requires a tokio runtime.
+    pub async fn get_chunk_blocking_as<T: ChunkData + Send + 'static>(&mut self, chunk_x: u8, chunk_z: u8) -> Result<T, ChunkLoadError> {
+        let payload = self.get_payload(chunk_x, chunk_z).await?;
+        let external = self.external.clone();
+        tokio::task::spawn_blocking(move || decode_payload(&payload, external.as_deref(), chunk_x, chunk_z))
+            .await
+            .map_err(std::io::Error::other)?
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+impl<R: AsyncRead + AsyncSeek + Unpin> Deref for AsyncRegionFileReader<R> {
+    type Target = RegionHeader;
+
+    fn deref(&self) -> &Self::Target {
+        &self.header
+    }
+}
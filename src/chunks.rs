@@ -1,21 +1,34 @@
-use std::io::Read;
+use std::borrow::Cow;
+use std::io::{Read, Write};
 
-use bytes::{Buf, Bytes};
-use crab_nbt::{Nbt, NbtTag};
+use crab_nbt::{Nbt, NbtCompound, NbtTag};
 use enum_utils::TryFromRepr;
 use flate2::bufread::{GzDecoder, ZlibDecoder};
+use flate2::write::{GzEncoder, ZlibEncoder};
 
+use crate::container::{ChunkData, RawChunk};
+use crate::decode_payload;
+use crate::chunks::editing::OwnedSection;
+use crate::chunks::block_entities::{BlockEntity, BlockEntityIterator};
 use crate::chunks::heightmaps::{Heightmap, HeightmapType};
+use crate::chunks::light::SectionLight;
 use crate::error::{malformed_chunk_str, ChunkLoadError};
 use crate::error::ChunkLoadError::*;
-use crate::chunks::sections::ChunkSection;
+use crate::chunks::sections::{BlockState, ChunkSection, SectionFormat};
+use crate::chunks::version::{BiomeFormat, ChunkFormat, DataVersion};
 
 pub mod sections;
+pub mod editing;
+pub mod block_entities;
+pub mod biomes;
+pub mod light;
 pub mod iterators;
 pub mod heightmaps;
+pub mod version;
+mod legacy;
 mod utils;
 
-#[derive(Debug, TryFromRepr)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromRepr)]
 #[repr(u8)]
 pub enum CompressionFormat {
     Gzip = 1,
@@ -24,6 +37,53 @@ pub enum CompressionFormat {
     Lz4 = 4,
     // There could be Custom = 127 here, but we couldn't support it anyway
 }
+impl CompressionFormat {
+    pub(crate) fn decompress<'a>(&self, compressed: &'a [u8]) -> std::io::Result<Cow<'a, [u8]>> {
+        Ok(match self {
+            // FIXME: Gzip & Zlib decoders constantly reallocate vec
+            //  (It appears the implementation is slightly stupid)
+            CompressionFormat::Gzip => {
+                let mut vec = Vec::new();
+                GzDecoder::new(compressed).read_to_end(&mut vec)?;
+                Cow::Owned(vec)
+            },
+            CompressionFormat::Zlib => {
+                let mut vec = Vec::new();
+                ZlibDecoder::new(compressed).read_to_end(&mut vec)?;
+                Cow::Owned(vec)
+            },
+            CompressionFormat::Lz4 => {
+                let mut vec = Vec::new();
+                lz4::Decoder::new(compressed)?.read_to_end(&mut vec)?;
+                Cow::Owned(vec)
+            },
+            CompressionFormat::Uncompressed => Cow::Borrowed(compressed)
+        })
+    }
+
+    pub(crate) fn compress(&self, data: &[u8]) -> std::io::Result<Vec<u8>> {
+        Ok(match self {
+            CompressionFormat::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(data)?;
+                encoder.finish()?
+            },
+            CompressionFormat::Zlib => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(data)?;
+                encoder.finish()?
+            },
+            CompressionFormat::Lz4 => {
+                let mut encoder = lz4::EncoderBuilder::new().build(Vec::new())?;
+                encoder.write_all(data)?;
+                let (vec, result) = encoder.finish();
+                result?;
+                vec
+            },
+            CompressionFormat::Uncompressed => data.to_vec()
+        })
+    }
+}
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum ChunkStatus {
@@ -56,6 +116,8 @@ impl TryFrom<&str> for ChunkStatus {
             "initialize_light" => ChunkStatus::InitializeLight,
             "spawn" => ChunkStatus::Spawn,
             "full" => ChunkStatus::Full,
+            // Only used by older versions
+            "heightmaps" => ChunkStatus::Spawn,
             s => return Err(MalformedChunk(format!("Chunk has unexpected status {s})")))
         })
     }
@@ -64,96 +126,263 @@ impl TryFrom<&str> for ChunkStatus {
 const HEIGHTMAPS_KEY: &'static str = "Heightmaps";
 const STATUS_KEY: &'static str = "Status";
 
+/// A chunk as stored in the region file.
+/// Chunks saved before 1.13 use numeric block ids, their sections are converted to palettes when loading.
+/// The conversion is not updated when `data` is modified.
+///
+/// Use [`Chunk::from_nbt`] to create a chunk from NBT, e.g. when it was loaded from somewhere else than a region file.
 #[derive(Debug)]
 pub struct Chunk {
     pub status: ChunkStatus,
-    pub data: Nbt
+    /// The version of the game that saved this chunk. Chunks saved before 1.9 don't have one.
+    pub data_version: Option<DataVersion>,
+    pub data: Nbt,
+    format: ChunkFormat,
+    converted_sections: Option<Vec<NbtTag>>
 }
 impl Chunk {
-    pub(crate) fn read(buf: &[u8]) -> Result<Self, ChunkLoadError> {
-        let size = buf.get(0..4)
-            .ok_or_else(malformed_chunk_str("Header is too short, should be 4 bytes"))?
-            .try_into()
-            .map(|b| u32::from_be_bytes(b) - 1)
-            .unwrap(); // converting this &[u8] into a [u8;4] will never fail
-        let compression_format = buf.get(4)
-            .ok_or_else(malformed_chunk_str("Chunk is too short for header (len<5)"))
-            .map(|x| CompressionFormat::try_from(*x))?
-            .map_err(|_| ChunkLoadError::UnknownCompressionFormat(buf[4]))?;
-
-        let mut decompressed: Box<dyn Buf>;
-        {
-            let compressed = &buf[5..((size+5) as usize)];
-            decompressed = match compression_format {
-                // FIXME: Gzip & Zlib decoders constantly reallocate vec
-                //  (It appears the implementation is slightly stupid)
-                CompressionFormat::Gzip => {
-                    let mut vec = Vec::new();
-                    GzDecoder::new(compressed).read_to_end(&mut vec)?;
-                    Box::new(Bytes::from(vec))
-                },
-                CompressionFormat::Zlib => {
-                    let mut vec = Vec::new();
-                    ZlibDecoder::new(compressed).read_to_end(&mut vec)?;
-                    Box::new(Bytes::from(vec))
-                },
-                CompressionFormat::Lz4 => {
-                    let mut vec = Vec::new();
-                    lz4::Decoder::new(compressed)?.read_to_end(&mut vec)?;
-                    Box::new(Bytes::from(vec))
-                },
-                CompressionFormat::Uncompressed => Box::new(compressed)
-            };
+    /// Parses the decompressed NBT of a chunk, detecting the format it was saved in.
+    pub fn from_nbt(nbt: Nbt) -> Result<Self, ChunkLoadError> {
+        let format = ChunkFormat::detect(&nbt);
+        let level = format.get_level(&nbt)?;
+        if format.numeric_blocks {
+            let status = legacy::get_legacy_status(level);
+            let converted_sections = Some(legacy::convert_sections(level)?);
+            return Ok(Chunk { status, data_version: format.version, data: nbt, format, converted_sections });
         }
-        let nbt = Nbt::read(&mut decompressed)?;
         // This is not a nice way to do it and fails various safetys.
         //  However: I don't care (right now)
         // FIXME: Don't.
-        let _ = nbt.get_compound(HEIGHTMAPS_KEY)
+        let _ = level.get_compound(HEIGHTMAPS_KEY)
             .ok_or_else(malformed_chunk_str("Chunk has no heightmaps"))?;
         Ok(Chunk {
-            status: nbt.get_string(STATUS_KEY)
-                .ok_or_else(malformed_chunk_str("Chunk has no status"))?
-                .as_str().try_into()?,
-            data: nbt
+            status: format.parse_status(level.get_string(STATUS_KEY)
+                .ok_or_else(malformed_chunk_str("Chunk has no status"))?)?,
+            data_version: format.version,
+            data: nbt,
+            format,
+            converted_sections: None
         })
     }
 
+    /// Parses a chunk from the payload format used within region sectors,
+    /// i.e. prefixed by its length and the compression byte.
+    /// Chunks stored in external files can't be read this way.
+    pub fn read(payload: &[u8]) -> Result<Self, ChunkLoadError> {
+        decode_payload(payload, None, 0, 0)
+    }
+
+    /// Serialises and compresses `data` into the payload format used within region sectors.
+    /// This is the inverse of [`Chunk::read`]. Chunks are written in the format they were loaded in,
+    /// the sections of pre-1.13 chunks are not written in their converted form.
+    pub fn write(&self, compression: CompressionFormat) -> std::io::Result<Vec<u8>> {
+        encode_nbt(&self.data, compression)
+    }
+
+    /// Returns where the biomes of this chunk are stored.
+    pub fn get_biome_format(&self) -> BiomeFormat {
+        self.format.biomes
+    }
+
     pub fn get_subchunks(&self) -> Result<SectionIterator<'_>, ChunkLoadError> {
+        let format = self.format.section;
         self.get_sections().map(|sections| SectionIterator {
-            section_tags: sections.iter()
+            section_tags: sections.iter(),
+            format
         })
     }
 
     pub fn get_subchunk(&self, index: usize) -> Result<ChunkSection<'_>, ChunkLoadError> {
-        parse_chunk(self.get_sections()?.get(index).ok_or(MissingSection)?)
+        parse_chunk(self.get_sections()?.get(index).ok_or(MissingSection)?, self.format.section)
+    }
+
+    /// Returns the section with the specified section y coordinate (i.e. block y / 16).
+    /// Returns None, if the chunk does not contain such a section.
+    pub fn get_subchunk_at(&self, section_y: i8) -> Result<Option<ChunkSection<'_>>, ChunkLoadError> {
+        let tag = self.get_sections()?.iter()
+            .find(|tag| tag.extract_compound().and_then(|compound| compound.get_byte("Y")) == Some(section_y));
+        match tag.map(|tag| parse_chunk(tag, self.format.section)) {
+            None | Some(Err(EmptySection)) => Ok(None),
+            Some(result) => result.map(Some)
+        }
+    }
+
+    /// Returns the light of the section with the specified section y coordinate.
+    /// Unlike [`Chunk::get_subchunk_at`], this includes sections that only store light.
+    /// Returns None, if the chunk does not contain such a section.
+    pub fn get_light_at(&self, section_y: i8) -> Result<Option<SectionLight<'_>>, ChunkLoadError> {
+        self.get_sections()?.iter()
+            .filter_map(NbtTag::extract_compound)
+            .find(|compound| compound.get_byte("Y") == Some(section_y))
+            .map(SectionLight::from_section)
+            .transpose()
+    }
+
+    /// Returns a modifiable copy of the section with the specified section y coordinate.
+    /// Returns None, if the chunk does not contain such a section.
+    pub fn get_owned_section(&self, section_y: i8) -> Result<Option<OwnedSection>, ChunkLoadError> {
+        self.get_sections()?.iter()
+            .filter_map(NbtTag::extract_compound)
+            .find(|compound| compound.get_byte("Y") == Some(section_y))
+            .map(|compound| OwnedSection::from_nbt(compound, self.format.section))
+            .transpose()
+    }
+
+    /// Replaces the section with the same y coordinate, or adds it if there is none.
+    /// Unused palette entries are removed and the section is stored in the format of this chunk.
+    /// Chunks saved before 1.13 can't be modified.
+    pub fn set_section(&mut self, mut section: OwnedSection) -> Result<(), ChunkLoadError> {
+        if self.format.numeric_blocks {
+            return Err(UnsupportedFormat);
+        }
+        section.compact();
+        let tag = NbtTag::Compound(section.to_nbt_with(self.format.section));
+        let key = self.format.sections_key;
+        let level = match self.format.level {
+            true => match get_mut(&mut self.data.root_tag, "Level") {
+                Some(NbtTag::Compound(level)) => level,
+                _ => return Err(MalformedChunk("Chunk has no Level compound".to_owned()))
+            },
+            false => &mut self.data.root_tag
+        };
+        let Some(NbtTag::List(sections)) = get_mut(level, key) else {
+            return Err(MalformedChunk("Chunk has no sections list object".to_owned()));
+        };
+        let existing = sections.iter_mut()
+            .find(|tag| tag.extract_compound().and_then(|compound| compound.get_byte("Y")) == Some(section.y));
+        match existing {
+            Some(existing) => *existing = tag,
+            None => sections.push(tag)
+        }
+        Ok(())
+    }
+
+    /// Returns the x and z coordinates of this chunk in chunk coordinates (i.e. block coordinates / 16).
+    pub fn get_position(&self) -> Result<[i32; 2], ChunkLoadError> {
+        let level = self.get_level()?;
+        Ok([
+            level.get_int("xPos").ok_or_else(malformed_chunk_str("Chunk has no x position"))?,
+            level.get_int("zPos").ok_or_else(malformed_chunk_str("Chunk has no z position"))?
+        ])
+    }
+
+    /// Returns the block at the absolute world coordinates.
+    /// Returns None, if the position is outside of this chunk or within a section that does not exist.
+    pub fn get_block_at(&self, x: i32, y: i32, z: i32) -> Result<Option<BlockState<'_>>, ChunkLoadError> {
+        if self.get_position()? != [x.div_euclid(16), z.div_euclid(16)] {
+            return Ok(None);
+        }
+        let Ok(section_y) = i8::try_from(y.div_euclid(16)) else {
+            return Ok(None);
+        };
+        Ok(self.get_subchunk_at(section_y)?.map(|section| *section.blocks.get_block(
+            x.rem_euclid(16) as u8, y.rem_euclid(16) as u8, z.rem_euclid(16) as u8
+        )))
+    }
+
+    /// Iterates over the block entities of this chunk.
+    /// Chunks without a block entity list are treated as having none.
+    pub fn get_block_entities(&self) -> Result<BlockEntityIterator<'_>, ChunkLoadError> {
+        let tags = match self.get_level()?.get(self.format.block_entities_key) {
+            None => [].iter(),
+            Some(NbtTag::List(tags)) => tags.iter(),
+            Some(_) => return Err(MalformedChunk("Block entities are not a list".to_owned()))
+        };
+        Ok(BlockEntityIterator { tags })
+    }
+
+    /// Returns the block entity at the absolute world coordinates.
+    /// Returns None, if there is no block entity at that position.
+    pub fn get_block_entity_at(&self, x: i32, y: i32, z: i32) -> Result<Option<BlockEntity<'_>>, ChunkLoadError> {
+        for block_entity in self.get_block_entities()? {
+            let block_entity = block_entity?;
+            if block_entity.position == [x, y, z] {
+                return Ok(Some(block_entity));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns the block at the absolute world coordinates together with its block entity, if it has one.
+    /// Returns None under the same conditions as [`Chunk::get_block_at`].
+    pub fn get_block_with_entity_at(&self, x: i32, y: i32, z: i32)
+        -> Result<Option<(BlockState<'_>, Option<BlockEntity<'_>>)>, ChunkLoadError> {
+        let Some(block) = self.get_block_at(x, y, z)? else {
+            return Ok(None);
+        };
+        Ok(Some((block, self.get_block_entity_at(x, y, z)?)))
     }
 
     fn get_sections(&self) -> Result<&Vec<NbtTag>, ChunkLoadError> {
-        self.data.get_list("sections")
+        if let Some(sections) = &self.converted_sections {
+            return Ok(sections);
+        }
+        self.get_level()?.get_list(self.format.sections_key)
             .ok_or_else(malformed_chunk_str("Chunk has no sections list object"))
     }
 
+    fn get_level(&self) -> Result<&NbtCompound, ChunkLoadError> {
+        self.format.get_level(&self.data)
+    }
+
+    /// Returns None, if the heightmap does not exist.
+    /// Chunks saved before 1.13 don't have any heightmaps in this format.
     pub fn get_heightmap(&self, heightmap: HeightmapType) -> Option<Heightmap<'_>> {
-        self.data.get_compound(HEIGHTMAPS_KEY).unwrap()
+        self.get_level().ok()?.get_compound(HEIGHTMAPS_KEY)?
             .get_long_array(heightmap.get_identifier())
-            .map(|nbt| Heightmap::new(nbt))
+            .map(|nbt| Heightmap::with_format(nbt, self.format.get_heightmap_bits(nbt.len()), self.format.packing))
+    }
+}
+
+impl ChunkData for Chunk {
+    fn from_nbt(nbt: Nbt) -> Result<Self, ChunkLoadError> {
+        Chunk::from_nbt(nbt)
     }
 }
 
-fn parse_chunk<'a>(tag: &'a NbtTag) -> Result<ChunkSection<'a>, ChunkLoadError> {
+/// Splits the payload stored in the region file into the compression byte and the compressed data.
+/// The compression byte may still contain the external flag.
+pub(crate) fn split_payload(buf: &[u8]) -> Result<(u8, &[u8]), ChunkLoadError> {
+    let size = buf.get(0..4)
+        .ok_or_else(malformed_chunk_str("Header is too short, should be 4 bytes"))?
+        .try_into()
+        .map(u32::from_be_bytes)
+        .unwrap() // converting this &[u8] into a [u8;4] will never fail
+        .checked_sub(1)
+        .ok_or_else(malformed_chunk_str("Chunk length does not include the compression format"))?;
+    let compression = *buf.get(4)
+        .ok_or_else(malformed_chunk_str("Chunk is too short for header (len<5)"))?;
+    let compressed = buf.get(5..(size as usize + 5))
+        .ok_or_else(malformed_chunk_str("Chunk length exceeds the allocated sectors"))?;
+    Ok((compression, compressed))
+}
+
+/// Serialises the NBT and compresses it into the payload format used within region sectors.
+/// The result is prefixed by its length (including the compression byte) and the compression byte.
+pub(crate) fn encode_nbt(nbt: &Nbt, compression: CompressionFormat) -> std::io::Result<Vec<u8>> {
+    RawChunk::encode(nbt, compression)?.to_payload()
+}
+
+fn get_mut<'a>(compound: &'a mut NbtCompound, key: &str) -> Option<&'a mut NbtTag> {
+    compound.child_tags.iter_mut()
+        .find(|(name, _)| name == key)
+        .map(|(_, tag)| tag)
+}
+
+fn parse_chunk(tag: &NbtTag, format: SectionFormat) -> Result<ChunkSection<'_>, ChunkLoadError> {
     tag.extract_compound()
         .ok_or_else(malformed_chunk_str("Chunk section is not a compound"))
-        .and_then(|compound| ChunkSection::new(compound))
+        .and_then(|compound| ChunkSection::new(compound, format))
 }
 
 pub struct SectionIterator<'a> {
-    section_tags: std::slice::Iter<'a, NbtTag>
+    section_tags: std::slice::Iter<'a, NbtTag>,
+    format: SectionFormat
 }
 impl<'a> Iterator for SectionIterator<'a> {
     type Item = Result<ChunkSection<'a>, ChunkLoadError>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.section_tags.next().map(|tag| parse_chunk(tag))
+        self.section_tags.next().map(|tag| parse_chunk(tag, self.format))
     }
 }
\ No newline at end of file
@@ -0,0 +1,103 @@
+use crab_nbt::NbtCompound;
+
+use crate::chunks::utils::{calculate_bits_per_biome, read_packed, Packing};
+use crate::error::{malformed_chunk_str, ChunkLoadError};
+
+static EMPTY_VEC_I64: Vec<i64> = Vec::new();
+
+/// Biomes are stored per cell of 4x4x4 blocks
+const CELLS_PER_AXIS: u8 = 4;
+const CELL_COUNT: u8 = CELLS_PER_AXIS * CELLS_PER_AXIS * CELLS_PER_AXIS;
+
+/// The biomes of a section, stored since 1.18.
+/// Coordinates are in cells of 4x4x4 blocks, i.e. block coordinates within the section divided by 4.
+#[derive(Debug)]
+pub struct SectionBiomes<'a> {
+    palette: Vec<&'a String>,
+    data: &'a Vec<i64>
+}
+impl<'a> SectionBiomes<'a> {
+    pub(crate) fn new(compound: &'a NbtCompound) -> Result<Self, ChunkLoadError> {
+        let palette = compound.get_list("palette")
+            .ok_or_else(malformed_chunk_str("Biomes have no palette"))?
+            .iter()
+            .map(|tag| tag.extract_string()
+                .ok_or_else(malformed_chunk_str("Biome palette entry is not a string")))
+            .collect::<Result<Vec<_>, _>>()?;
+        if palette.is_empty() {
+            return Err(ChunkLoadError::MalformedChunk("Biome palette is empty".to_owned()));
+        }
+        let biomes = SectionBiomes {
+            palette,
+            data: compound.get_long_array("data").unwrap_or(&EMPTY_VEC_I64)
+        };
+        // Checked once here, so that looking up biomes can't fail
+        let bits = calculate_bits_per_biome(biomes.palette.len());
+        if bits > 0 && !biomes.data.is_empty() {
+            for i in 0..CELL_COUNT as u16 {
+                let value = read_packed(biomes.data, i, bits, Packing::Aligned)
+                    .ok_or_else(malformed_chunk_str("Biome data is too short"))?;
+                if value as usize >= biomes.palette.len() {
+                    return Err(ChunkLoadError::MalformedChunk("Biome data exceeds the palette".to_owned()));
+                }
+            }
+        }
+        Ok(biomes)
+    }
+
+    pub fn get_palette(&self) -> &Vec<&'a String> {
+        &self.palette
+    }
+
+    /// Returns the namespaced id of the biome in the cell.
+    pub fn get_biome(&self, x: u8, y: u8, z: u8) -> &'a String {
+        if x >= CELLS_PER_AXIS || y >= CELLS_PER_AXIS || z >= CELLS_PER_AXIS {
+            panic!("components of ({x},{y},{z}) are not in [0;4)")
+        }
+        self.get_by_index(x + CELLS_PER_AXIS * z + CELLS_PER_AXIS * CELLS_PER_AXIS * y)
+    }
+
+    fn get_by_index(&self, i: u8) -> &'a String {
+        let bits = calculate_bits_per_biome(self.palette.len());
+        // Sections with a single biome don't need any data
+        if bits == 0 || self.data.is_empty() {
+            return self.palette[0];
+        }
+        let value = read_packed(self.data, i as u16, bits, Packing::Aligned).unwrap(); // checked in new
+        self.palette[value as usize]
+    }
+}
+impl<'a> IntoIterator for &'a SectionBiomes<'a> {
+    type Item = &'a String;
+    type IntoIter = BiomeIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        BiomeIter { biomes: self, i: 0 }
+    }
+}
+
+/// Iterates over the biomes of all 64 cells, ordered by x, then z, then y.
+pub struct BiomeIter<'a> {
+    biomes: &'a SectionBiomes<'a>,
+    i: u8
+}
+impl<'a> BiomeIter<'a> {
+    pub fn with_coordinates(self) -> impl Iterator<Item = ([u8;3], &'a String)> {
+        self.enumerate().map(|(i, biome)| {
+            let i = i as u8;
+            ([i % CELLS_PER_AXIS, i / (CELLS_PER_AXIS * CELLS_PER_AXIS), (i / CELLS_PER_AXIS) % CELLS_PER_AXIS], biome)
+        })
+    }
+}
+impl<'a> Iterator for BiomeIter<'a> {
+    type Item = &'a String;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.i >= CELL_COUNT {
+            return None;
+        }
+        let biome = self.biomes.get_by_index(self.i);
+        self.i += 1;
+        Some(biome)
+    }
+}
@@ -0,0 +1,52 @@
+use crab_nbt::{NbtCompound, NbtTag};
+
+use crate::error::{malformed_chunk_str, ChunkLoadError};
+
+/// Keys describing the block entity itself, rather than its contents
+const HEADER_KEYS: [&str; 5] = ["id", "x", "y", "z", "keepPacked"];
+
+/// A block entity (also called tile entity), e.g. a chest, sign or spawner.
+#[derive(Debug, Clone, Copy)]
+pub struct BlockEntity<'a> {
+    /// The namespaced id of the block entity type.
+    /// Chunks saved before 1.11 use names like `Chest` instead.
+    pub id: &'a String,
+    /// The absolute world coordinates of the block
+    pub position: [i32; 3],
+    /// The complete NBT of the block entity, including its id and position
+    pub nbt: &'a NbtCompound
+}
+impl<'a> BlockEntity<'a> {
+    pub(crate) fn new(compound: &'a NbtCompound) -> Result<Self, ChunkLoadError> {
+        let coordinate = |key| compound.get_int(key)
+            .ok_or_else(|| ChunkLoadError::MalformedChunk(format!("Block entity has no {key} coordinate")));
+        Ok(BlockEntity {
+            id: compound.get_string("id").ok_or_else(malformed_chunk_str("Block entity has no id"))?,
+            position: [coordinate("x")?, coordinate("y")?, coordinate("z")?],
+            nbt: compound
+        })
+    }
+
+    /// Iterates over the contents of the block entity, i.e. all tags but the id and position.
+    pub fn get_data(&self) -> impl Iterator<Item = &'a (String, NbtTag)> {
+        self.nbt.child_tags.iter()
+            .filter(|(key, _)| !HEADER_KEYS.contains(&key.as_str()))
+    }
+
+    pub fn get(&self, key: &str) -> Option<&'a NbtTag> {
+        self.get_data().find(|(name, _)| name == key).map(|(_, tag)| tag)
+    }
+}
+
+pub struct BlockEntityIterator<'a> {
+    pub(crate) tags: std::slice::Iter<'a, NbtTag>
+}
+impl<'a> Iterator for BlockEntityIterator<'a> {
+    type Item = Result<BlockEntity<'a>, ChunkLoadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.tags.next().map(|tag| tag.extract_compound()
+            .ok_or_else(malformed_chunk_str("Block entity is not a compound"))
+            .and_then(BlockEntity::new))
+    }
+}
@@ -0,0 +1,202 @@
+use crab_nbt::{NbtCompound, NbtTag};
+
+use crate::chunks::sections::{ChunkSection, OwnedBlockState, SectionFormat};
+use crate::chunks::utils::{calculate_bits_per_block, packed_length, read_packed, write_packed, Packing};
+use crate::error::{malformed_chunk_str, ChunkLoadError};
+
+const SECTION_VOLUME: usize = 16 * 16 * 16;
+/// Keys of the section compound holding block states, in any format
+const BLOCK_STATE_KEYS: [&str; 3] = ["block_states", "Palette", "BlockStates"];
+
+/// A section owning its data, so that blocks can be changed.
+/// All other data of the section, like biomes and light, is kept unchanged.
+/// The light is not updated when blocks change.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OwnedSection {
+    pub y: i8,
+    palette: Vec<OwnedBlockState>,
+    /// Palette indices, packed without spanning longs. Empty while the palette has a single entry.
+    data: Vec<i64>,
+    bits_per_block: u8,
+    other: NbtCompound
+}
+impl OwnedSection {
+    /// Creates a section filled with a single block state.
+    pub fn new(y: i8, fill: impl Into<OwnedBlockState>) -> Self {
+        OwnedSection::filled(y, fill.into(), NbtCompound::new())
+    }
+
+    fn filled(y: i8, entry: OwnedBlockState, other: NbtCompound) -> Self {
+        OwnedSection { y, palette: vec![entry], data: Vec::new(), bits_per_block: 0, other }
+    }
+
+    pub(crate) fn from_nbt(compound: &NbtCompound, format: SectionFormat) -> Result<Self, ChunkLoadError> {
+        let y = compound.get_byte("Y").ok_or_else(malformed_chunk_str("Section missing Y value"))?;
+        let other: NbtCompound = compound.child_tags.iter()
+            .filter(|(key, _)| key != "Y" && !BLOCK_STATE_KEYS.contains(&key.as_str()))
+            .cloned()
+            .collect();
+        let blocks = match ChunkSection::new(compound, format) {
+            // Sections only storing light don't have any blocks
+            Err(ChunkLoadError::EmptySection) => {
+                return Ok(OwnedSection::filled(y, OwnedBlockState::new("minecraft:air"), other));
+            },
+            result => result?.blocks
+        };
+        if blocks.palette.is_empty() {
+            return Err(ChunkLoadError::MalformedChunk("Section has an empty palette".to_owned()));
+        }
+
+        let palette = blocks.palette.iter().copied().map(OwnedBlockState::from).collect();
+        let mut section = OwnedSection { y, palette, data: Vec::new(), bits_per_block: 0, other };
+        if blocks.data.is_empty() || section.palette.len() == 1 {
+            return Ok(section);
+        }
+        let bits = calculate_bits_per_block(section.palette.len());
+        let mut indices = Vec::with_capacity(SECTION_VOLUME);
+        for i in 0..SECTION_VOLUME as u16 {
+            let value = read_packed(blocks.data, i, bits, blocks.packing)
+                .ok_or_else(malformed_chunk_str("Block state data is too short"))?;
+            if value as usize >= section.palette.len() {
+                return Err(ChunkLoadError::MalformedChunk("Block state data exceeds the palette".to_owned()));
+            }
+            indices.push(value as u16);
+        }
+        section.set_indices(&indices);
+        Ok(section)
+    }
+
+    pub fn get_block(&self, x: u8, y: u8, z: u8) -> &OwnedBlockState {
+        let i = get_index(x, y, z);
+        // Sections consisting of a single block state don't store any data
+        let index = if self.data.is_empty() {
+            0
+        } else {
+            read_packed(&self.data, i, self.bits_per_block, Packing::Aligned).unwrap()
+        };
+        &self.palette[index as usize]
+    }
+
+    /// Replaces the block at the coordinates within the section.
+    /// The block state is added to the palette, if it is not part of it yet.
+    pub fn set_block(&mut self, x: u8, y: u8, z: u8, state: impl Into<OwnedBlockState>) {
+        let i = get_index(x, y, z);
+        let state = state.into();
+        let value = match self.palette.iter().position(|entry| *entry == state) {
+            Some(value) => value,
+            None => {
+                self.palette.push(state);
+                self.palette.len() - 1
+            }
+        };
+        if self.palette.len() == 1 {
+            return;
+        }
+        let bits = calculate_bits_per_block(self.palette.len());
+        if bits != self.bits_per_block {
+            let indices = self.get_indices();
+            self.set_indices(&indices);
+        }
+        write_packed(&mut self.data, i, bits, Packing::Aligned, value as u64);
+    }
+
+    /// Sets every block within the section to the block state.
+    pub fn fill(&mut self, state: impl Into<OwnedBlockState>) {
+        self.palette = vec![state.into()];
+        self.data = Vec::new();
+        self.bits_per_block = 0;
+    }
+
+    /// Returns the block states that may occur in this section.
+    /// Entries might not be used anymore, see [`OwnedSection::compact`].
+    pub fn get_palette(&self) -> &[OwnedBlockState] {
+        &self.palette
+    }
+
+    /// Removes all palette entries that aren't used by any block, shrinking the data if possible.
+    pub fn compact(&mut self) {
+        let indices = self.get_indices();
+        let mut used = vec![false; self.palette.len()];
+        for index in &indices {
+            used[*index as usize] = true;
+        }
+        if used.iter().all(|used| *used) {
+            return;
+        }
+        let mut remapped = vec![0u16; self.palette.len()];
+        let mut palette = Vec::new();
+        for (i, entry) in std::mem::take(&mut self.palette).into_iter().enumerate() {
+            if used[i] {
+                remapped[i] = palette.len() as u16;
+                palette.push(entry);
+            }
+        }
+        self.palette = palette;
+        let indices: Vec<u16> = indices.into_iter().map(|index| remapped[index as usize]).collect();
+        self.set_indices(&indices);
+    }
+
+    /// Converts the section into NBT in the format used since 1.18.
+    pub fn to_nbt(&self) -> NbtCompound {
+        self.to_nbt_with(SectionFormat::CURRENT)
+    }
+
+    pub(crate) fn to_nbt_with(&self, format: SectionFormat) -> NbtCompound {
+        let mut compound = NbtCompound::new();
+        compound.put("Y".to_owned(), self.y);
+        let palette = NbtTag::List(self.palette.iter().map(|state| NbtTag::Compound(state.to_compound())).collect());
+        if format.nested {
+            let mut block_states = NbtCompound::new();
+            block_states.put("palette".to_owned(), palette);
+            if self.palette.len() > 1 {
+                block_states.put("data".to_owned(), self.pack(format.packing));
+            }
+            compound.put("block_states".to_owned(), block_states);
+        } else {
+            // Before 1.18 the data is stored even for a single block state
+            compound.put("Palette".to_owned(), palette);
+            compound.put("BlockStates".to_owned(), self.pack(format.packing));
+        }
+        compound.child_tags.extend(self.other.child_tags.iter().cloned());
+        compound
+    }
+
+    fn get_indices(&self) -> Vec<u16> {
+        if self.data.is_empty() {
+            return vec![0; SECTION_VOLUME];
+        }
+        (0..SECTION_VOLUME as u16)
+            .map(|i| read_packed(&self.data, i, self.bits_per_block, Packing::Aligned).unwrap() as u16)
+            .collect()
+    }
+
+    /// Packs the indices using the bits required by the current palette.
+    fn set_indices(&mut self, indices: &[u16]) {
+        if self.palette.len() == 1 {
+            self.data = Vec::new();
+            self.bits_per_block = 0;
+            return;
+        }
+        self.bits_per_block = calculate_bits_per_block(self.palette.len());
+        self.data = pack(indices, self.bits_per_block, Packing::Aligned);
+    }
+
+    fn pack(&self, packing: Packing) -> Vec<i64> {
+        pack(&self.get_indices(), calculate_bits_per_block(self.palette.len()), packing)
+    }
+}
+
+fn pack(indices: &[u16], bits_per_block: u8, packing: Packing) -> Vec<i64> {
+    let mut data = vec![0i64; packed_length(indices.len(), bits_per_block, packing)];
+    for (i, index) in indices.iter().enumerate() {
+        write_packed(&mut data, i as u16, bits_per_block, packing, *index as u64);
+    }
+    data
+}
+
+fn get_index(x: u8, y: u8, z: u8) -> u16 {
+    if x >= 16 || y >= 16 || z >= 16 {
+        panic!("components of ({x},{y},{z}) are not in [0;16)")
+    }
+    x as u16 + 16 * z as u16 + 256 * y as u16
+}
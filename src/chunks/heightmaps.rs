@@ -1,4 +1,4 @@
-use crate::chunks::utils::{get_index_offset_form, unpack_value};
+use crate::chunks::utils::{read_packed, Packing};
 
 
 const BITS_PER_VALUE: u8 = 9;
@@ -22,19 +22,25 @@ impl HeightmapType {
 }
 
 pub struct Heightmap<'a> {
-    data: &'a Vec<i64>
+    data: &'a Vec<i64>,
+    bits_per_value: u8,
+    packing: Packing
 }
 impl<'a> Heightmap<'a> {
     pub fn new(data: &'a Vec<i64>) -> Self {
-        Heightmap { data: data }
+        Heightmap { data: data, bits_per_value: BITS_PER_VALUE, packing: Packing::Aligned }
+    }
+
+    pub(crate) fn with_format(data: &'a Vec<i64>, bits_per_value: u8, packing: Packing) -> Self {
+        Heightmap { data, bits_per_value, packing }
     }
 
     /// Returns the distance from the world floor at the relative xz position in a chunk.
     /// Note that the resulting value is unsigned and thus requires subtracting 64 to get a y-value.
     pub fn get_at(&self, x: u8, z: u8) -> u16 {
         let i = x as u16 + (z as u16)*16;
-        let (index, offset) = get_index_offset_form(i, BITS_PER_VALUE, i64::BITS as u8);
-        unpack_value::<u16>(self.data[index], offset, BITS_PER_VALUE)
+        read_packed(self.data, i, self.bits_per_value, self.packing)
+            .expect("Heightmap data is too short") as u16
     }
 }
 impl<'a> IntoIterator for &'a Heightmap<'a> {
@@ -44,17 +50,14 @@ impl<'a> IntoIterator for &'a Heightmap<'a> {
 
     fn into_iter(self) -> Self::IntoIter {
         HeightmapIterator {
-            data: self.data, i: 0,
-            index: 0, offset: 0
+            heightmap: self, i: 0
         }
     }
 }
 
 pub struct HeightmapIterator<'a> {
-    data: &'a Vec<i64>,
-    i: u16,
-    index: usize,
-    offset: u8
+    heightmap: &'a Heightmap<'a>,
+    i: u16
 }
 impl<'a> HeightmapIterator<'a> {
     pub fn with_coordinates(self) -> impl Iterator<Item = ([u8;2], u16)> {
@@ -68,14 +71,8 @@ impl<'a> Iterator for HeightmapIterator<'a> {
         if self.i >= HEIGHTMAP_LENGTH {
             return None;
         }
-        let result = self.data.get(self.index)
-            .map(|x| unpack_value::<u16>(*x, self.offset, BITS_PER_VALUE));
-        self.offset += BITS_PER_VALUE;
-        if self.offset >= (i64::BITS as u8 - BITS_PER_VALUE) {
-            self.offset = 0;
-            self.index += 1;
-        }
+        let result = read_packed(self.heightmap.data, self.i, self.heightmap.bits_per_value, self.heightmap.packing);
         self.i += 1;
-        result
+        result.map(|height| height as u16)
     }
 }
\ No newline at end of file
@@ -1,20 +1,18 @@
-use crate::chunks::{sections::{BlockState, SectionBlocks}, utils::{calculate_bits_per_block, unpack_value}};
+use crate::chunks::{sections::{BlockState, SectionBlocks}, utils::{calculate_bits_per_block, read_packed}};
+
+const SECTION_VOLUME: u16 = 16 * 16 * 16;
 
 pub struct BlockIter<'a> {
     section: &'a SectionBlocks<'a>,
-    values_returned: u16,
-    bits_per_block: u8,
-    array_index: usize,
-    offset: u8
+    i: u16,
+    bits_per_block: u8
 }
 impl<'a> BlockIter<'a> {
     pub(super) fn new(section: &'a SectionBlocks<'a>) -> Self {
         BlockIter {
             section: section,
-            values_returned: 0,
-            bits_per_block: calculate_bits_per_block(&section.palette),
-            array_index: 0,
-            offset: 0
+            i: 0,
+            bits_per_block: calculate_bits_per_block(section.palette.len())
         }
     }
 
@@ -33,24 +31,20 @@ impl<'a> Iterator for BlockIter<'a> {
     type Item = &'a BlockState<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.values_returned += 1;
-        let block_index;
-        if self.section.data.len() == 0 {
-            block_index = if self.values_returned > 4096 { None } else { Some(0) };
-        } else {
-            block_index = self.section.data.get(self.array_index)
-                .map(|value| unpack_value(*value, self.offset, self.bits_per_block));
-            self.offset += self.bits_per_block;
-            if u32::from(self.offset) >= i64::BITS {
-                self.offset = 0;
-                self.array_index += 1;
-            }
+        if self.i >= SECTION_VOLUME {
+            return None;
         }
-        block_index.map(|x| &self.section.palette[x])
+        let block_index = if self.section.data.is_empty() {
+            Some(0)
+        } else {
+            read_packed(self.section.data, self.i, self.bits_per_block, self.section.packing)
+        };
+        self.i += 1;
+        block_index.map(|x| &self.section.palette[x as usize])
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
-        // TODO: Validate this against the iterator state
-        (4096, Some(4096))
+        let remaining = (SECTION_VOLUME - self.i) as usize;
+        (remaining, Some(remaining))
     }
 }
@@ -0,0 +1,467 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use bytes::Bytes;
+use crab_nbt::{NbtCompound, NbtTag};
+
+use crate::chunks::ChunkStatus;
+use crate::chunks::utils::{calculate_bits_per_block, pack_values};
+use crate::error::{malformed_chunk_str, ChunkLoadError};
+
+const COLORS: [&str; 16] = [
+    "white", "orange", "magenta", "light_blue", "yellow", "lime", "pink", "gray",
+    "light_gray", "cyan", "purple", "blue", "brown", "green", "red", "black"
+];
+const WOODS: [&str; 6] = ["oak", "spruce", "birch", "jungle", "acacia", "dark_oak"];
+const STONE_SLABS: [&str; 8] = [
+    "smooth_stone", "sandstone", "petrified_oak", "cobblestone", "brick", "stone_brick", "nether_brick", "quartz"
+];
+
+type Properties = Vec<(&'static str, String)>;
+type LegacyState = (Cow<'static, str>, Properties);
+
+/// Legacy chunks only know whether their decoration has been generated.
+pub(crate) fn get_legacy_status(level: &NbtCompound) -> ChunkStatus {
+    if level.get_bool("TerrainPopulated").unwrap_or(false) {
+        ChunkStatus::Full
+    } else {
+        ChunkStatus::Carvers
+    }
+}
+
+/// Converts the blocks of a legacy chunk into sections with palettes, the way they are stored today.
+/// Everything but the block data is copied from the original sections.
+pub(crate) fn convert_sections(level: &NbtCompound) -> Result<Vec<NbtTag>, ChunkLoadError> {
+    match level.get_list("Sections") {
+        Some(sections) => sections.iter()
+            .map(|tag| tag.extract_compound()
+                .ok_or_else(malformed_chunk_str("Chunk section is not a compound"))
+                .and_then(convert_anvil_section))
+            .collect(),
+        // McRegion chunks store the whole column in one array instead
+        None => convert_mcregion_column(level)
+    }
+}
+
+fn convert_anvil_section(section: &NbtCompound) -> Result<NbtTag, ChunkLoadError> {
+    let blocks = get_byte_array(section, "Blocks", 4096)?;
+    let data = get_byte_array(section, "Data", 2048)?;
+    // Add holds the upper 4 bits of block ids above 255, which only exist in modded worlds
+    let add = match section.get("Add") {
+        None => None,
+        Some(_) => Some(get_byte_array(section, "Add", 2048)?)
+    };
+    let block_states = convert_blocks(|i| {
+        let upper = add.as_ref().map_or(0, |add| get_nibble(add, i));
+        ((upper as u16) << 8 | blocks[i] as u16, get_nibble(&data, i))
+    });
+
+    let mut converted: NbtCompound = section.child_tags.iter()
+        .filter(|(name, _)| !matches!(name.as_str(), "Blocks" | "Data" | "Add"))
+        .cloned()
+        .collect();
+    converted.put("block_states".to_owned(), block_states);
+    Ok(NbtTag::Compound(converted))
+}
+
+fn convert_mcregion_column(level: &NbtCompound) -> Result<Vec<NbtTag>, ChunkLoadError> {
+    let blocks = get_byte_array(level, "Blocks", 32768)?;
+    let data = get_byte_array(level, "Data", 16384)?;
+    let light: Vec<_> = ["SkyLight", "BlockLight"].into_iter()
+        .filter_map(|key| Some((key, get_byte_array(level, key, 16384).ok()?)))
+        .collect();
+
+    Ok((0..8).map(|section_y| {
+        // McRegion orders blocks by x, then z, then y, while sections use y, then z, then x
+        let index = |i: usize| (i & 15) << 11 | (i >> 4 & 15) << 7 | (section_y * 16 + (i >> 8));
+        let mut section = NbtCompound::new();
+        section.put("Y".to_owned(), section_y as i8);
+        section.put("block_states".to_owned(), convert_blocks(|i| (blocks[index(i)] as u16, get_nibble(&data, index(i)))));
+        for (key, values) in &light {
+            let mut converted = vec![0u8; 2048];
+            for (i, byte) in converted.iter_mut().enumerate() {
+                *byte = get_nibble(values, index(2 * i)) | get_nibble(values, index(2 * i + 1)) << 4;
+            }
+            section.put(key.to_string(), Bytes::from(converted));
+        }
+        NbtTag::Compound(section)
+    }).collect())
+}
+
+/// Builds the block_states compound of a section from the numeric id and data value at each index.
+fn convert_blocks(block_at: impl Fn(usize) -> (u16, u8)) -> NbtCompound {
+    let mut lookup = HashMap::new();
+    let mut palette = Vec::new();
+    let indices: Vec<u64> = (0..4096).map(|i| {
+        let (id, data) = block_at(i);
+        *lookup.entry((id, data)).or_insert_with(|| {
+            palette.push(NbtTag::Compound(convert_block(id, data)));
+            palette.len() as u64 - 1
+        })
+    }).collect();
+
+    let mut block_states = NbtCompound::new();
+    // Like in the game, sections of a single block state don't store any data
+    if palette.len() > 1 {
+        block_states.put("data".to_owned(), pack_values(&indices, calculate_bits_per_block(palette.len())));
+    }
+    block_states.put("palette".to_owned(), NbtTag::List(palette));
+    block_states
+}
+
+/// Creates the palette entry for a numeric block id and data value.
+/// Blocks unknown to vanilla 1.12 are named `legacy:<id>`, keeping the data value as a property.
+fn convert_block(id: u16, data: u8) -> NbtCompound {
+    let (name, properties) = u8::try_from(id).ok()
+        .and_then(|id| get_legacy_state(id, data))
+        .map(|(name, properties)| (format!("minecraft:{name}"), properties))
+        .unwrap_or_else(|| (format!("legacy:{id}"), vec![("data", data.to_string())]));
+
+    let mut compound = NbtCompound::new();
+    compound.put("Name".to_owned(), name);
+    if !properties.is_empty() {
+        compound.put("Properties".to_owned(), properties.into_iter()
+            .map(|(key, value)| (key.to_owned(), NbtTag::String(value)))
+            .collect::<NbtCompound>());
+    }
+    compound
+}
+
+/// Maps the block ids of 1.12 to their current names.
+/// Properties are only converted where they are part of the data value and don't depend on neighbouring blocks.
+/// Other properties, e.g. most orientations, are left for the game to fill in.
+fn get_legacy_state(id: u8, data: u8) -> Option<LegacyState> {
+    let color = COLORS[(data & 15) as usize];
+    match id {
+        0 => plain("air"),
+        1 => variant(&["stone", "granite", "polished_granite", "diorite", "polished_diorite", "andesite", "polished_andesite"], data),
+        2 => plain("grass_block"),
+        3 => variant(&["dirt", "coarse_dirt", "podzol"], data),
+        4 => plain("cobblestone"),
+        5 => wooden(data, "planks", Vec::new()),
+        6 => wooden(data & 7, "sapling", Vec::new()),
+        7 => plain("bedrock"),
+        8 | 9 => with("water", vec![("level", (data & 15).to_string())]),
+        10 | 11 => with("lava", vec![("level", (data & 15).to_string())]),
+        12 => variant(&["sand", "red_sand"], data),
+        13 => plain("gravel"),
+        14 => plain("gold_ore"),
+        15 => plain("iron_ore"),
+        16 => plain("coal_ore"),
+        17 => log(data & 3, data),
+        18 => wooden(data & 3, "leaves", Vec::new()),
+        19 => variant(&["sponge", "wet_sponge"], data),
+        20 => plain("glass"),
+        21 => plain("lapis_ore"),
+        22 => plain("lapis_block"),
+        23 => plain("dispenser"),
+        24 => variant(&["sandstone", "chiseled_sandstone", "cut_sandstone"], data),
+        25 => plain("note_block"),
+        // The color of beds is stored in their block entity
+        26 => plain("red_bed"),
+        27 => plain("powered_rail"),
+        28 => plain("detector_rail"),
+        29 => plain("sticky_piston"),
+        30 => plain("cobweb"),
+        31 => variant(&["dead_bush", "short_grass", "fern"], data),
+        32 => plain("dead_bush"),
+        33 => plain("piston"),
+        34 => plain("piston_head"),
+        35 => colored(color, "wool"),
+        36 => plain("moving_piston"),
+        37 => plain("dandelion"),
+        38 => variant(&[
+            "poppy", "blue_orchid", "allium", "azure_bluet", "red_tulip",
+            "orange_tulip", "white_tulip", "pink_tulip", "oxeye_daisy"
+        ], data),
+        39 => plain("brown_mushroom"),
+        40 => plain("red_mushroom"),
+        41 => plain("gold_block"),
+        42 => plain("iron_block"),
+        43 => match data {
+            8 => plain("smooth_stone"),
+            9 => plain("smooth_sandstone"),
+            15 => plain("smooth_quartz"),
+            _ => slab(STONE_SLABS.get(data as usize)?, "double")
+        },
+        44 => slab(STONE_SLABS[(data & 7) as usize], slab_half(data)),
+        45 => plain("bricks"),
+        46 => plain("tnt"),
+        47 => plain("bookshelf"),
+        48 => plain("mossy_cobblestone"),
+        49 => plain("obsidian"),
+        50 => torch("torch", "wall_torch", data, Vec::new()),
+        51 => plain("fire"),
+        52 => plain("spawner"),
+        53 => plain("oak_stairs"),
+        54 => plain("chest"),
+        55 => with("redstone_wire", vec![("power", (data & 15).to_string())]),
+        56 => plain("diamond_ore"),
+        57 => plain("diamond_block"),
+        58 => plain("crafting_table"),
+        59 => with("wheat", age(data & 7)),
+        60 => with("farmland", vec![("moisture", (data & 7).to_string())]),
+        61 => with("furnace", lit(false)),
+        62 => with("furnace", lit(true)),
+        63 => plain("oak_sign"),
+        64 => plain("oak_door"),
+        65 => plain("ladder"),
+        66 => plain("rail"),
+        67 => plain("cobblestone_stairs"),
+        68 => plain("oak_wall_sign"),
+        69 => plain("lever"),
+        70 => plain("stone_pressure_plate"),
+        71 => plain("iron_door"),
+        72 => plain("oak_pressure_plate"),
+        73 => with("redstone_ore", lit(false)),
+        74 => with("redstone_ore", lit(true)),
+        75 => torch("redstone_torch", "redstone_wall_torch", data, lit(false)),
+        76 => torch("redstone_torch", "redstone_wall_torch", data, lit(true)),
+        77 => plain("stone_button"),
+        78 => with("snow", vec![("layers", ((data & 7) + 1).to_string())]),
+        79 => plain("ice"),
+        80 => plain("snow_block"),
+        81 => with("cactus", age(data & 15)),
+        82 => plain("clay"),
+        83 => with("sugar_cane", age(data & 15)),
+        84 => plain("jukebox"),
+        85 => plain("oak_fence"),
+        86 => plain("carved_pumpkin"),
+        87 => plain("netherrack"),
+        88 => plain("soul_sand"),
+        89 => plain("glowstone"),
+        90 => plain("nether_portal"),
+        91 => plain("jack_o_lantern"),
+        92 => with("cake", vec![("bites", (data & 7).to_string())]),
+        93 => with("repeater", powered(false)),
+        94 => with("repeater", powered(true)),
+        95 => colored(color, "stained_glass"),
+        96 => plain("oak_trapdoor"),
+        97 => variant(&[
+            "infested_stone", "infested_cobblestone", "infested_stone_bricks",
+            "infested_mossy_stone_bricks", "infested_cracked_stone_bricks", "infested_chiseled_stone_bricks"
+        ], data),
+        98 => variant(&["stone_bricks", "mossy_stone_bricks", "cracked_stone_bricks", "chiseled_stone_bricks"], data),
+        99 => plain(if data == 10 || data == 15 { "mushroom_stem" } else { "brown_mushroom_block" }),
+        100 => plain(if data == 10 || data == 15 { "mushroom_stem" } else { "red_mushroom_block" }),
+        101 => plain("iron_bars"),
+        102 => plain("glass_pane"),
+        103 => plain("melon"),
+        104 => with("pumpkin_stem", age(data & 7)),
+        105 => with("melon_stem", age(data & 7)),
+        106 => plain("vine"),
+        107 => plain("oak_fence_gate"),
+        108 => plain("brick_stairs"),
+        109 => plain("stone_brick_stairs"),
+        110 => plain("mycelium"),
+        111 => plain("lily_pad"),
+        112 => plain("nether_bricks"),
+        113 => plain("nether_brick_fence"),
+        114 => plain("nether_brick_stairs"),
+        115 => with("nether_wart", age(data & 3)),
+        116 => plain("enchanting_table"),
+        117 => plain("brewing_stand"),
+        118 => match data & 3 {
+            0 => plain("cauldron"),
+            level => with("water_cauldron", vec![("level", level.to_string())])
+        },
+        119 => plain("end_portal"),
+        120 => with("end_portal_frame", vec![("eye", (data & 4 != 0).to_string())]),
+        121 => plain("end_stone"),
+        122 => plain("dragon_egg"),
+        123 => with("redstone_lamp", lit(false)),
+        124 => with("redstone_lamp", lit(true)),
+        125 => wooden(data & 7, "slab", slab_type("double")),
+        126 => wooden(data & 7, "slab", slab_type(slab_half(data))),
+        127 => with("cocoa", age(data >> 2)),
+        128 => plain("sandstone_stairs"),
+        129 => plain("emerald_ore"),
+        130 => plain("ender_chest"),
+        131 => plain("tripwire_hook"),
+        132 => plain("tripwire"),
+        133 => plain("emerald_block"),
+        134 => plain("spruce_stairs"),
+        135 => plain("birch_stairs"),
+        136 => plain("jungle_stairs"),
+        137 => plain("command_block"),
+        138 => plain("beacon"),
+        139 => variant(&["cobblestone_wall", "mossy_cobblestone_wall"], data),
+        140 => plain("flower_pot"),
+        141 => with("carrots", age(data & 7)),
+        142 => with("potatoes", age(data & 7)),
+        143 => plain("oak_button"),
+        // The type of skulls is stored in their block entity
+        144 => plain("skeleton_skull"),
+        145 => variant(&["anvil", "chipped_anvil", "damaged_anvil"], data >> 2),
+        146 => plain("trapped_chest"),
+        147 => plain("light_weighted_pressure_plate"),
+        148 => plain("heavy_weighted_pressure_plate"),
+        149 => with("comparator", powered(false)),
+        150 => with("comparator", powered(true)),
+        151 => with("daylight_detector", vec![("inverted", "false".to_owned())]),
+        152 => plain("redstone_block"),
+        153 => plain("nether_quartz_ore"),
+        154 => plain("hopper"),
+        155 => match data {
+            0 => plain("quartz_block"),
+            1 => plain("chiseled_quartz_block"),
+            2..=4 => with("quartz_pillar", axis(data - 2)),
+            _ => None
+        },
+        156 => plain("quartz_stairs"),
+        157 => plain("activator_rail"),
+        158 => plain("dropper"),
+        159 => colored(color, "terracotta"),
+        160 => colored(color, "stained_glass_pane"),
+        161 => wooden((data & 3) + 4, "leaves", Vec::new()),
+        162 => log((data & 3) + 4, data),
+        163 => plain("acacia_stairs"),
+        164 => plain("dark_oak_stairs"),
+        165 => plain("slime_block"),
+        166 => plain("barrier"),
+        167 => plain("iron_trapdoor"),
+        168 => variant(&["prismarine", "prismarine_bricks", "dark_prismarine"], data),
+        169 => plain("sea_lantern"),
+        170 => plain("hay_block"),
+        171 => colored(color, "carpet"),
+        172 => plain("terracotta"),
+        173 => plain("coal_block"),
+        174 => plain("packed_ice"),
+        // Upper halves don't store their type, so they are named after the first variant
+        175 => variant(&["sunflower", "lilac", "tall_grass", "large_fern", "rose_bush", "peony"], data & 7)
+            .map(|(name, _)| (name, vec![("half", if data & 8 != 0 { "upper" } else { "lower" }.to_owned())])),
+        // The color of banners is stored in their block entity
+        176 => plain("white_banner"),
+        177 => plain("white_wall_banner"),
+        178 => with("daylight_detector", vec![("inverted", "true".to_owned())]),
+        179 => variant(&["red_sandstone", "chiseled_red_sandstone", "cut_red_sandstone"], data),
+        180 => plain("red_sandstone_stairs"),
+        181 => match data {
+            8 => plain("smooth_red_sandstone"),
+            _ => slab("red_sandstone", "double")
+        },
+        182 => slab("red_sandstone", slab_half(data)),
+        183 => plain("spruce_fence_gate"),
+        184 => plain("birch_fence_gate"),
+        185 => plain("jungle_fence_gate"),
+        186 => plain("dark_oak_fence_gate"),
+        187 => plain("acacia_fence_gate"),
+        188 => plain("spruce_fence"),
+        189 => plain("birch_fence"),
+        190 => plain("jungle_fence"),
+        191 => plain("dark_oak_fence"),
+        192 => plain("acacia_fence"),
+        193 => plain("spruce_door"),
+        194 => plain("birch_door"),
+        195 => plain("jungle_door"),
+        196 => plain("acacia_door"),
+        197 => plain("dark_oak_door"),
+        198 => plain("end_rod"),
+        199 => plain("chorus_plant"),
+        200 => with("chorus_flower", age(data & 7)),
+        201 => plain("purpur_block"),
+        202 => with("purpur_pillar", axis(data >> 2)),
+        203 => plain("purpur_stairs"),
+        204 => slab("purpur", "double"),
+        205 => slab("purpur", slab_half(data)),
+        206 => plain("end_stone_bricks"),
+        207 => with("beetroots", age(data & 3)),
+        208 => plain("dirt_path"),
+        209 => plain("end_gateway"),
+        210 => plain("repeating_command_block"),
+        211 => plain("chain_command_block"),
+        212 => with("frosted_ice", age(data & 3)),
+        213 => plain("magma_block"),
+        214 => plain("nether_wart_block"),
+        215 => plain("red_nether_bricks"),
+        216 => with("bone_block", axis(data >> 2)),
+        217 => plain("structure_void"),
+        218 => plain("observer"),
+        219..=234 => colored(COLORS[(id - 219) as usize], "shulker_box"),
+        235..=250 => colored(COLORS[(id - 235) as usize], "glazed_terracotta"),
+        251 => colored(color, "concrete"),
+        252 => colored(color, "concrete_powder"),
+        255 => plain("structure_block"),
+        _ => None
+    }
+}
+
+fn plain(name: &'static str) -> Option<LegacyState> {
+    Some((Cow::Borrowed(name), Vec::new()))
+}
+
+fn with(name: &'static str, properties: Properties) -> Option<LegacyState> {
+    Some((Cow::Borrowed(name), properties))
+}
+
+fn variant(names: &[&'static str], data: u8) -> Option<LegacyState> {
+    plain(names.get(data as usize)?)
+}
+
+fn colored(color: &str, block: &str) -> Option<LegacyState> {
+    Some((Cow::Owned(format!("{color}_{block}")), Vec::new()))
+}
+
+fn wooden(wood: u8, block: &str, properties: Properties) -> Option<LegacyState> {
+    Some((Cow::Owned(format!("{}_{block}", WOODS.get(wood as usize)?)), properties))
+}
+
+/// Logs store their orientation in the upper two bits, with 3 meaning bark on all sides.
+fn log(wood: u8, data: u8) -> Option<LegacyState> {
+    match data >> 2 {
+        3 => wooden(wood, "wood", axis(0)),
+        orientation => wooden(wood, "log", axis(orientation))
+    }
+}
+
+fn slab(material: &str, kind: &str) -> Option<LegacyState> {
+    Some((Cow::Owned(format!("{material}_slab")), slab_type(kind)))
+}
+
+fn slab_half(data: u8) -> &'static str {
+    if data & 8 != 0 { "top" } else { "bottom" }
+}
+
+fn slab_type(kind: &str) -> Properties {
+    vec![("type", kind.to_owned())]
+}
+
+/// Torches standing on the ground have data 5 (or 0), all others are attached to a wall.
+fn torch(standing: &'static str, wall: &'static str, data: u8, mut properties: Properties) -> Option<LegacyState> {
+    match data {
+        1..=4 => {
+            properties.push(("facing", ["east", "west", "south", "north"][data as usize - 1].to_owned()));
+            with(wall, properties)
+        },
+        _ => with(standing, properties)
+    }
+}
+
+fn age(age: u8) -> Properties {
+    vec![("age", age.to_string())]
+}
+
+fn lit(lit: bool) -> Properties {
+    vec![("lit", lit.to_string())]
+}
+
+fn powered(powered: bool) -> Properties {
+    vec![("powered", powered.to_string())]
+}
+
+/// Legacy axes are ordered y, x, z
+fn axis(axis: u8) -> Properties {
+    vec![("axis", ["y", "x", "z"].get(axis as usize).unwrap_or(&"y").to_string())]
+}
+
+fn get_byte_array(compound: &NbtCompound, key: &str, length: usize) -> Result<Bytes, ChunkLoadError> {
+    compound.get(key)
+        .and_then(NbtTag::extract_byte_array)
+        .filter(|array| array.len() == length)
+        .ok_or_else(|| ChunkLoadError::MalformedChunk(format!("Legacy chunk has no valid {key} array")))
+}
+
+/// Values of nibble arrays are stored in the lower half of the byte first.
+fn get_nibble(array: &[u8], index: usize) -> u8 {
+    (array[index / 2] >> (index % 2 * 4)) & 15
+}
@@ -0,0 +1,90 @@
+use crab_nbt::{NbtCompound, NbtTag};
+
+use crate::error::ChunkLoadError;
+
+const LIGHT_ARRAY_LENGTH: usize = 2048;
+
+/// Both light arrays of a section, read without its block states.
+/// Sections above or below the terrain often only store light.
+#[derive(Debug, Clone, Copy)]
+pub struct SectionLight<'a> {
+    /// Light emitted by blocks. None if the section does not store it.
+    pub block_light: Option<LightArray<'a>>,
+    /// Light from the sky. None if the section does not store it.
+    pub sky_light: Option<LightArray<'a>>
+}
+impl<'a> SectionLight<'a> {
+    pub(crate) fn from_section(compound: &'a NbtCompound) -> Result<Self, ChunkLoadError> {
+        Ok(SectionLight {
+            block_light: LightArray::from_section(compound, "BlockLight")?,
+            sky_light: LightArray::from_section(compound, "SkyLight")?
+        })
+    }
+}
+
+/// The light levels of a section, one nibble per block.
+#[derive(Debug, Clone, Copy)]
+pub struct LightArray<'a> {
+    data: &'a [u8]
+}
+impl<'a> LightArray<'a> {
+    /// Reads the array stored under the key, returning None if the section does not store it.
+    pub(crate) fn from_section(compound: &'a NbtCompound, key: &str) -> Result<Option<Self>, ChunkLoadError> {
+        match compound.get(key) {
+            None => Ok(None),
+            Some(NbtTag::ByteArray(data)) if data.len() == LIGHT_ARRAY_LENGTH => Ok(Some(LightArray { data })),
+            Some(_) => Err(ChunkLoadError::MalformedChunk(format!("{key} is not a byte array of {LIGHT_ARRAY_LENGTH} bytes")))
+        }
+    }
+
+    /// Returns the light level in [0;15] at the position within the section.
+    pub fn get(&self, x: u8, y: u8, z: u8) -> u8 {
+        if x >= 16 || y >= 16 || z >= 16 {
+            panic!("components of ({x},{y},{z}) are not in [0;16)")
+        }
+        self.get_by_index(x as usize + 16 * z as usize + 256 * y as usize)
+    }
+
+    fn get_by_index(&self, i: usize) -> u8 {
+        // Even indices are stored in the lower half of the byte
+        (self.data[i / 2] >> (i % 2 * 4)) & 0xf
+    }
+}
+impl<'a> IntoIterator for &LightArray<'a> {
+    type Item = u8;
+    type IntoIter = LightIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        LightIter { light: *self, i: 0 }
+    }
+}
+
+/// Iterates over the light levels of all 4096 blocks, ordered by x, then z, then y.
+pub struct LightIter<'a> {
+    light: LightArray<'a>,
+    i: usize
+}
+impl LightIter<'_> {
+    pub fn with_coordinates(self) -> impl Iterator<Item = ([u8;3], u8)> {
+        self.enumerate().map(|(i, level)| {
+            ([(i % 16) as u8, (i / 256) as u8, (i / 16 % 16) as u8], level)
+        })
+    }
+}
+impl Iterator for LightIter<'_> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.i >= 2 * LIGHT_ARRAY_LENGTH {
+            return None;
+        }
+        let level = self.light.get_by_index(self.i);
+        self.i += 1;
+        Some(level)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = 2 * LIGHT_ARRAY_LENGTH - self.i;
+        (remaining, Some(remaining))
+    }
+}
@@ -1,26 +1,64 @@
+use std::collections::BTreeMap;
 use std::fmt::Display;
+use std::str::FromStr;
 
 use crab_nbt::{NbtCompound, NbtTag};
 
+use crate::chunks::biomes::SectionBiomes;
 use crate::chunks::iterators::BlockIter;
-use crate::chunks::utils::{calculate_bits_per_block, get_index_offset_form, unpack_value};
-use crate::error::{malformed_chunk_str, ChunkLoadError};
+use crate::chunks::light::{LightArray, SectionLight};
+use crate::chunks::utils::{calculate_bits_per_block, read_packed, Packing};
+use crate::error::{malformed_chunk_str, ChunkLoadError, ParseBlockStateError};
 use crate::error::ChunkLoadError::*;
 
 static EMPTY_VEC_I64: Vec<i64> = Vec::new();
 static EMPTY_VEC_BLOCK_PROPERTIES: Vec<(String, NbtTag)> = Vec::new();
 
+/// Where and how the block states of a section are stored, depending on the chunk's data version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SectionFormat {
+    /// Since 1.18 palette and data are nested within a block_states compound.
+    /// Before that they are stored as Palette and BlockStates in the section itself.
+    pub(crate) nested: bool,
+    pub(crate) packing: Packing
+}
+impl SectionFormat {
+    pub(crate) const CURRENT: SectionFormat = SectionFormat { nested: true, packing: Packing::Aligned };
+
+    pub(crate) fn new(nested: bool, packing: Packing) -> Self {
+        SectionFormat { nested, packing }
+    }
+}
+
 #[derive(Debug)]
 pub struct ChunkSection<'a> {
     pub y: i8,
-    pub blocks: SectionBlocks<'a>
+    pub blocks: SectionBlocks<'a>,
+    /// The biomes stored in the section, if the chunk was saved by 1.18 or later.
+    /// Older chunks store their biomes for the whole chunk instead, see [`crate::chunks::Chunk::get_biome_format`].
+    pub biomes: Option<SectionBiomes<'a>>,
+    /// Light emitted by blocks. None if the section does not store it, which the game treats as no light.
+    pub block_light: Option<LightArray<'a>>,
+    /// Light from the sky. None if the section does not store it,
+    /// in which case the game derives it from the sections above (or uses 0, if the dimension has no sky light).
+    pub sky_light: Option<LightArray<'a>>
 }
 impl<'a> ChunkSection<'a> {
-    pub(crate) fn new(compound: &'a NbtCompound) -> Result<Self, ChunkLoadError> {
+    pub(crate) fn new(compound: &'a NbtCompound, format: SectionFormat) -> Result<Self, ChunkLoadError> {
+        let blocks = if format.nested {
+            let block_states = compound.get_compound("block_states").ok_or(EmptySection)?;
+            SectionBlocks::new(block_states.get_list("palette"), block_states.get_long_array("data"), format.packing)?
+        } else {
+            let palette = compound.get_list("Palette").ok_or(EmptySection)?;
+            SectionBlocks::new(Some(palette), compound.get_long_array("BlockStates"), format.packing)?
+        };
+        let light = SectionLight::from_section(compound)?;
         Ok(Self {
             y: compound.get_byte("Y").ok_or_else(malformed_chunk_str("Section missing Y value"))?,
-            blocks: SectionBlocks::new(compound.get_compound("block_states")
-                .ok_or(EmptySection)?)?
+            blocks,
+            biomes: compound.get_compound("biomes").map(SectionBiomes::new).transpose()?,
+            block_light: light.block_light,
+            sky_light: light.sky_light
         })
     }
 }
@@ -28,11 +66,12 @@ impl<'a> ChunkSection<'a> {
 #[derive(Debug)]
 pub struct SectionBlocks<'a> {
     pub(super) palette: Vec<BlockState<'a>>,
-    pub(super) data: &'a Vec<i64>
+    pub(super) data: &'a Vec<i64>,
+    pub(super) packing: Packing
 }
 impl<'a> SectionBlocks<'a> {
-    fn new(compound: &'a NbtCompound) -> Result<Self, ChunkLoadError> {
-        let palette = match compound.get_list("palette") {
+    fn new(palette: Option<&'a Vec<NbtTag>>, data: Option<&'a Vec<i64>>, packing: Packing) -> Result<Self, ChunkLoadError> {
+        let palette = match palette {
             None => Vec::new(),
             Some(palette_raw) => {
                 let mut palette = Vec::with_capacity(palette_raw.len());
@@ -47,8 +86,8 @@ impl<'a> SectionBlocks<'a> {
         };
         Ok(Self {
             palette: palette,
-            data: compound.get_long_array("data")
-                .unwrap_or(&EMPTY_VEC_I64)
+            data: data.unwrap_or(&EMPTY_VEC_I64),
+            packing
         })
     }
 
@@ -56,14 +95,19 @@ impl<'a> SectionBlocks<'a> {
         &self.palette
     }
 
-    pub fn get_block(&'a self, x: u8, y: u8, z: u8) -> &'a BlockState<'a> {
+    pub fn get_block(&self, x: u8, y: u8, z: u8) -> &BlockState<'a> {
         if x >= 16 || y >= 16 || z >= 16 {
             panic!("components of ({x},{y},{z}) are not in [0;16)")
         }
+        // Sections consisting of a single block state don't store any data
+        if self.data.is_empty() {
+            return &self.palette[0];
+        }
         let i: u16 = x as u16 + 16*(z as u16) + 16*16*(y as u16);
-        let bpb = calculate_bits_per_block(&self.palette);
-        let (index, offset) = get_index_offset_form(i, bpb, i64::BITS as u8);
-        &self.palette[unpack_value::<usize>(self.data[index], offset, bpb)]
+        let bpb = calculate_bits_per_block(self.palette.len());
+        let value = read_packed(self.data, i, bpb, self.packing)
+            .expect("Block state data is too short");
+        &self.palette[value as usize]
     }
 }
 impl<'a> IntoIterator for &'a SectionBlocks<'a> {
@@ -75,7 +119,7 @@ impl<'a> IntoIterator for &'a SectionBlocks<'a> {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct BlockState<'a> {
     /// The namespaced id of the Block
     pub name: &'a String,
@@ -83,7 +127,7 @@ pub struct BlockState<'a> {
     pub properties: &'a Vec<(String, NbtTag)>
 }
 impl<'a> BlockState<'a> {
-    fn new(compound: &'a NbtCompound) -> Option<Self> {
+    pub(crate) fn new(compound: &'a NbtCompound) -> Option<Self> {
         let name = compound.get_string("Name")?;
         let properties = compound.get_compound("Properties")
             .map(|x| &x.child_tags)
@@ -96,11 +140,148 @@ impl<'a> Display for BlockState<'a> {
         write!(f, "{}", self.name)?;
         if !self.properties.is_empty() {
             write!(f, "[")?;
-            for (name, value) in self.properties {
-                write!(f, "{}={:?}", name, value)?;
+            for (i, (name, value)) in self.properties.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ",")?;
+                }
+                write!(f, "{}={}", name, format_property(value))?;
             }
             write!(f, "]")?;
         }
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+/// Property values are stored as strings, other tags are only expected in modified data.
+fn format_property(value: &NbtTag) -> String {
+    match value {
+        NbtTag::String(value) => value.clone(),
+        NbtTag::Byte(value) => value.to_string(),
+        NbtTag::Short(value) => value.to_string(),
+        NbtTag::Int(value) => value.to_string(),
+        NbtTag::Long(value) => value.to_string(),
+        value => format!("{value:?}")
+    }
+}
+
+/// A block state that owns its name and properties, e.g. to build sections from scratch.
+/// Properties are kept sorted, so states are equal regardless of the order their properties were given in.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct OwnedBlockState {
+    /// The namespaced id of the Block
+    pub name: String,
+    pub properties: BTreeMap<String, String>
+}
+impl OwnedBlockState {
+    pub fn new(name: impl Into<String>) -> Self {
+        OwnedBlockState { name: name.into(), properties: BTreeMap::new() }
+    }
+
+    pub fn with_property(mut self, name: impl Into<String>, value: impl ToString) -> Self {
+        self.set_property(name, value);
+        self
+    }
+
+    pub fn set_property(&mut self, name: impl Into<String>, value: impl ToString) {
+        self.properties.insert(name.into(), value.to_string());
+    }
+
+    pub fn get_property(&self, name: &str) -> Option<&str> {
+        self.properties.get(name).map(String::as_str)
+    }
+
+    /// Returns None, if the property does not exist or is not `true` or `false`.
+    pub fn get_bool(&self, name: &str) -> Option<bool> {
+        self.get_property(name)?.parse().ok()
+    }
+
+    /// Returns None, if the property does not exist or is not a number.
+    pub fn get_int(&self, name: &str) -> Option<i32> {
+        self.get_property(name)?.parse().ok()
+    }
+
+    /// Converts the block state into a palette entry.
+    pub fn to_compound(&self) -> NbtCompound {
+        let mut compound = NbtCompound::new();
+        compound.put("Name".to_owned(), self.name.clone());
+        if !self.properties.is_empty() {
+            let properties: NbtCompound = self.properties.iter()
+                .map(|(name, value)| (name.clone(), NbtTag::String(value.clone())))
+                .collect();
+            compound.put("Properties".to_owned(), properties);
+        }
+        compound
+    }
+}
+impl<'a> From<BlockState<'a>> for OwnedBlockState {
+    fn from(state: BlockState<'a>) -> Self {
+        OwnedBlockState {
+            name: state.name.clone(),
+            properties: state.properties.iter()
+                .map(|(name, value)| (name.clone(), format_property(value)))
+                .collect()
+        }
+    }
+}
+impl From<&OwnedBlockState> for OwnedBlockState {
+    fn from(state: &OwnedBlockState) -> Self {
+        state.clone()
+    }
+}
+impl<'a> PartialEq<BlockState<'a>> for OwnedBlockState {
+    fn eq(&self, other: &BlockState<'a>) -> bool {
+        self.name == *other.name && self.properties.len() == other.properties.len()
+            && other.properties.iter().all(|(name, value)| self.get_property(name) == Some(&format_property(value)))
+    }
+}
+impl Display for OwnedBlockState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)?;
+        if !self.properties.is_empty() {
+            let properties: Vec<String> = self.properties.iter()
+                .map(|(name, value)| format!("{name}={value}"))
+                .collect();
+            write!(f, "[{}]", properties.join(","))?;
+        }
+        Ok(())
+    }
+}
+/// Parses the syntax used by commands, e.g. `minecraft:oak_stairs[facing=north,half=top]`.
+/// Names without a namespace are in the `minecraft` namespace.
+impl FromStr for OwnedBlockState {
+    type Err = ParseBlockStateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = |reason: &str| ParseBlockStateError(format!("{reason} in {s:?}"));
+        let (name, properties) = match s.split_once('[') {
+            None => (s, None),
+            Some((name, rest)) => (name, Some(rest.strip_suffix(']').ok_or_else(|| error("Missing ]"))?))
+        };
+        let name = name.trim();
+        if name.is_empty() || name.contains([']', '=', ',']) || name.contains(char::is_whitespace) {
+            return Err(error("Invalid name"));
+        }
+        if name.split_once(':').is_some_and(|(namespace, path)| namespace.is_empty() || path.is_empty() || path.contains(':')) {
+            return Err(error("Invalid namespace"));
+        }
+        let mut state = OwnedBlockState::new(if name.contains(':') {
+            name.to_owned()
+        } else {
+            format!("minecraft:{name}")
+        });
+        // Empty brackets are allowed
+        let properties = properties.filter(|properties| !properties.trim().is_empty());
+        for property in properties.into_iter().flat_map(|properties| properties.split(',')) {
+            let (key, value) = property.split_once('=').ok_or_else(|| error("Property without value"))?;
+            let (key, value) = (key.trim(), value.trim());
+            let invalid = |part: &str| part.is_empty() || part.contains(['=', '[', ']']) || part.contains(char::is_whitespace);
+            if invalid(key) || invalid(value) {
+                return Err(error("Invalid property"));
+            }
+            if state.properties.insert(key.to_owned(), value.to_owned()).is_some() {
+                return Err(error("Duplicate property"));
+            }
+        }
+        Ok(state)
+    }
+}
@@ -1,5 +1,4 @@
 use std::fmt::Debug;
-use crate::chunks::sections::BlockState;
 
 pub(crate) fn unpack_value<I>(value: i64, offset: u8, bits_per_value: u8) -> I 
     where I: TryFrom<u64, Error: Debug>
@@ -12,15 +11,90 @@ pub(crate) fn unpack_value<I>(value: i64, offset: u8, bits_per_value: u8) -> I
     return ((value as u64 & mask) >> offset).try_into().unwrap();
 }
 
-pub(crate) fn calculate_bits_per_block<'a>(palette: &Vec<BlockState<'a>>) -> u8 {
-    let x = palette.len() - 1;
+pub(crate) fn calculate_bits_per_block(palette_length: usize) -> u8 {
+    let x = palette_length - 1;
     // TODO: Is this really the fastest way to do it?
     ((x.checked_ilog2().unwrap_or(0) + 1) as u8).max(4)
 }
 
+/// Unlike block states, biomes have no minimum and use no data at all for a single biome.
+pub(crate) fn calculate_bits_per_biome(palette_length: usize) -> u8 {
+    match palette_length {
+        0 | 1 => 0,
+        length => ((length - 1).ilog2() + 1) as u8
+    }
+}
+
 pub(crate) fn get_index_offset_form(i: u16, bits_per_value: u8, packed_bits: u8) -> (usize, u8) {
-    let values_per_long = packed_bits / bits_per_value;
-    let index = i as usize / values_per_long as usize;
-    let offset = ((i * bits_per_value as u16) % packed_bits as u16) as u8;
+    let values_per_long = (packed_bits / bits_per_value) as u16;
+    let index = (i / values_per_long) as usize;
+    // The unused bits at the end of each long must not be counted
+    let offset = ((i % values_per_long) * bits_per_value as u16) as u8;
     (index, offset)
-}
\ No newline at end of file
+}
+
+/// How values are laid out in packed long arrays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Packing {
+    /// Values never span two longs, leaving the upper bits unused (since 1.16)
+    Aligned,
+    /// Values are packed back to back and may span two longs (before 1.16)
+    Spanning
+}
+/// Reads the i-th value out of a packed long array.
+/// Returns None, if the array is too short.
+pub(crate) fn read_packed(data: &[i64], i: u16, bits_per_value: u8, packing: Packing) -> Option<u64> {
+    match packing {
+        Packing::Aligned => {
+            let (index, offset) = get_index_offset_form(i, bits_per_value, i64::BITS as u8);
+            data.get(index).map(|value| unpack_value(*value, offset, bits_per_value))
+        },
+        Packing::Spanning => {
+            let bit = i as usize * bits_per_value as usize;
+            let (index, offset) = (bit / 64, (bit % 64) as u8);
+            if offset + bits_per_value <= 64 {
+                return data.get(index).map(|value| unpack_value(*value, offset, bits_per_value));
+            }
+            // The lower bits are at the end of this long, the upper bits at the start of the next one
+            let low_bits = 64 - offset;
+            let low: u64 = unpack_value(*data.get(index)?, offset, low_bits);
+            let high: u64 = unpack_value(*data.get(index + 1)?, 0, bits_per_value - low_bits);
+            Some(low | high << low_bits)
+        }
+    }
+}
+
+/// Packs the values into longs the way block states are stored since 1.16, i.e. without spanning values across longs.
+pub(crate) fn pack_values(values: &[u64], bits_per_value: u8) -> Vec<i64> {
+    let values_per_long = 64 / bits_per_value as usize;
+    values.chunks(values_per_long)
+        .map(|chunk| chunk.iter().enumerate()
+            .fold(0u64, |long, (i, value)| long | value << (i * bits_per_value as usize)) as i64)
+        .collect()
+}
+/// Returns the number of longs needed to store the values.
+pub(crate) fn packed_length(count: usize, bits_per_value: u8, packing: Packing) -> usize {
+    match packing {
+        Packing::Aligned => count.div_ceil(64 / bits_per_value as usize),
+        Packing::Spanning => (count * bits_per_value as usize).div_ceil(64)
+    }
+}
+
+/// Replaces the i-th value in a packed long array. The array must be long enough to hold the value.
+pub(crate) fn write_packed(data: &mut [i64], i: u16, bits_per_value: u8, packing: Packing, value: u64) {
+    let (index, offset) = match packing {
+        Packing::Aligned => get_index_offset_form(i, bits_per_value, i64::BITS as u8),
+        Packing::Spanning => {
+            let bit = i as usize * bits_per_value as usize;
+            (bit / 64, (bit % 64) as u8)
+        }
+    };
+    let mask = (1u64 << bits_per_value) - 1;
+    data[index] = ((data[index] as u64 & !(mask << offset)) | (value & mask) << offset) as i64;
+    // Only spanning values can exceed the long
+    if offset + bits_per_value > 64 {
+        let high_bits = offset + bits_per_value - 64;
+        let high_mask = (1u64 << high_bits) - 1;
+        data[index + 1] = ((data[index + 1] as u64 & !high_mask) | (value >> (64 - offset)) & high_mask) as i64;
+    }
+}
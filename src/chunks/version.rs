@@ -0,0 +1,182 @@
+use std::fmt::Display;
+
+use crab_nbt::NbtCompound;
+
+use crate::chunks::ChunkStatus;
+use crate::chunks::sections::SectionFormat;
+use crate::chunks::utils::Packing;
+use crate::error::ChunkLoadError;
+
+/// The version of the game that saved a chunk, stored as `DataVersion` since 1.9 (15w32a).
+/// Every snapshot and release has its own, increasing data version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DataVersion(pub i32);
+impl DataVersion {
+    /// 17w47a replaced numeric block ids with namespaced block states
+    pub const FLATTENING: DataVersion = DataVersion(1451);
+    /// 18w43a introduced the chunk statuses used today
+    pub const STATUS_REWORK: DataVersion = DataVersion(1901);
+    /// 19w36a started storing biomes in 4x4x4 cells
+    pub const BIOME_CELLS: DataVersion = DataVersion(2203);
+    /// 20w17a stopped spanning packed values across longs
+    pub const ALIGNED_PACKING: DataVersion = DataVersion(2529);
+    /// 21w37a moved block states and biomes into palette containers within the sections
+    pub const SECTION_CONTAINERS: DataVersion = DataVersion(2834);
+    /// 21w43a removed the Level compound, moving its contents to the root
+    pub const REMOVED_LEVEL: DataVersion = DataVersion(2844);
+
+    /// Returns the name of the release with exactly this data version, e.g. "1.20.4".
+    /// Returns None for snapshots and unknown versions.
+    pub fn get_release_name(&self) -> Option<&'static str> {
+        RELEASES.iter()
+            .find(|(version, _)| *version == self.0)
+            .map(|(_, name)| *name)
+    }
+
+    /// Returns the name of the latest release at or before this data version.
+    /// Snapshots map to the release they are based on, not the one they lead up to.
+    pub fn get_latest_release(&self) -> Option<&'static str> {
+        RELEASES.iter()
+            .take_while(|(version, _)| *version <= self.0)
+            .last()
+            .map(|(_, name)| *name)
+    }
+}
+impl From<i32> for DataVersion {
+    fn from(value: i32) -> Self {
+        DataVersion(value)
+    }
+}
+impl Display for DataVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.get_release_name() {
+            Some(name) => write!(f, "{} ({name})", self.0),
+            None => write!(f, "{}", self.0)
+        }
+    }
+}
+
+/// Data versions of all releases since they were introduced, in ascending order
+const RELEASES: &[(i32, &str)] = &[
+    (169, "1.9"), (175, "1.9.1"), (176, "1.9.2"), (183, "1.9.3"), (184, "1.9.4"),
+    (510, "1.10"), (511, "1.10.1"), (512, "1.10.2"),
+    (819, "1.11"), (921, "1.11.1"), (922, "1.11.2"),
+    (1139, "1.12"), (1241, "1.12.1"), (1343, "1.12.2"),
+    (1519, "1.13"), (1628, "1.13.1"), (1631, "1.13.2"),
+    (1952, "1.14"), (1957, "1.14.1"), (1963, "1.14.2"), (1968, "1.14.3"), (1976, "1.14.4"),
+    (2225, "1.15"), (2227, "1.15.1"), (2230, "1.15.2"),
+    (2566, "1.16"), (2567, "1.16.1"), (2578, "1.16.2"), (2580, "1.16.3"), (2584, "1.16.4"), (2586, "1.16.5"),
+    (2724, "1.17"), (2730, "1.17.1"),
+    (2860, "1.18"), (2865, "1.18.1"), (2975, "1.18.2"),
+    (3105, "1.19"), (3117, "1.19.1"), (3120, "1.19.2"), (3218, "1.19.3"), (3337, "1.19.4"),
+    (3463, "1.20"), (3465, "1.20.1"), (3578, "1.20.2"), (3698, "1.20.3"), (3700, "1.20.4"),
+    (3837, "1.20.5"), (3839, "1.20.6"),
+    (3953, "1.21"), (3955, "1.21.1"), (4080, "1.21.2"), (4082, "1.21.3"), (4189, "1.21.4"),
+    (4325, "1.21.5"), (4435, "1.21.6"), (4438, "1.21.7"), (4440, "1.21.8"),
+];
+
+/// Where the biomes of a chunk are stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BiomeFormat {
+    /// Numeric ids for each of the 16x16 columns in `Level.Biomes` (before 1.15)
+    Columns,
+    /// Numeric ids for 4x4x4 cells in `Level.Biomes` (1.15 to 1.17)
+    Cells,
+    /// A palette container of namespaced ids in every section (since 1.18)
+    SectionPalette
+}
+
+/// The layout of a chunk's NBT, as determined by its data version.
+/// All version-dependent decisions are made here.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ChunkFormat {
+    pub(crate) version: Option<DataVersion>,
+    /// Sections store numeric block ids, which are converted when loading the chunk
+    pub(crate) numeric_blocks: bool,
+    /// Everything but the data version is wrapped in a Level compound
+    pub(crate) level: bool,
+    pub(crate) sections_key: &'static str,
+    pub(crate) block_entities_key: &'static str,
+    pub(crate) section: SectionFormat,
+    pub(crate) packing: Packing,
+    pub(crate) biomes: BiomeFormat
+}
+impl ChunkFormat {
+    /// Determines the format of the chunk.
+    /// Chunks without a data version are either from before 1.9, if they have a Level compound, or current.
+    pub(crate) fn detect(nbt: &NbtCompound) -> Self {
+        let version = nbt.get_int("DataVersion").map(DataVersion);
+        let level = match version {
+            Some(version) => version < DataVersion::REMOVED_LEVEL,
+            None => nbt.get_compound("Level").is_some()
+        };
+        let numeric_blocks = level && version.is_none_or(|version| version < DataVersion::FLATTENING);
+        let packing = match version {
+            Some(version) if version < DataVersion::ALIGNED_PACKING => Packing::Spanning,
+            _ => Packing::Aligned
+        };
+        let biomes = match version {
+            _ if !level => BiomeFormat::SectionPalette,
+            Some(version) if version >= DataVersion::SECTION_CONTAINERS => BiomeFormat::SectionPalette,
+            Some(version) if version >= DataVersion::BIOME_CELLS => BiomeFormat::Cells,
+            _ => BiomeFormat::Columns
+        };
+        let section = if numeric_blocks {
+            // Converted sections use the current format
+            SectionFormat::CURRENT
+        } else {
+            SectionFormat::new(version.is_none_or(|version| version >= DataVersion::SECTION_CONTAINERS), packing)
+        };
+        ChunkFormat {
+            version,
+            numeric_blocks,
+            level,
+            sections_key: if level { "Sections" } else { "sections" },
+            block_entities_key: if level { "TileEntities" } else { "block_entities" },
+            section,
+            packing,
+            biomes
+        }
+    }
+
+    /// Returns the compound containing the chunk's data, i.e. the Level compound for chunks saved before 1.18.
+    pub(crate) fn get_level<'a>(&self, nbt: &'a NbtCompound) -> Result<&'a NbtCompound, ChunkLoadError> {
+        if !self.level {
+            return Ok(nbt);
+        }
+        nbt.get_compound("Level")
+            .ok_or_else(|| ChunkLoadError::MalformedChunk("Chunk has no Level compound".to_owned()))
+    }
+
+    /// Parses the status name, including the names used before 1.14.
+    pub(crate) fn parse_status(&self, name: &str) -> Result<ChunkStatus, ChunkLoadError> {
+        if self.version.is_some_and(|version| version < DataVersion::STATUS_REWORK) {
+            let status = match name.strip_prefix("minecraft:").unwrap_or(name) {
+                "base" => Some(ChunkStatus::Surface),
+                "carved" => Some(ChunkStatus::Carvers),
+                "liquid_carved" => Some(ChunkStatus::LiquidCarvers),
+                "decorated" => Some(ChunkStatus::Features),
+                "lighted" => Some(ChunkStatus::Light),
+                "mobs_spawned" | "finalized" => Some(ChunkStatus::Spawn),
+                "fullchunk" | "postprocessed" => Some(ChunkStatus::Full),
+                _ => None
+            };
+            if let Some(status) = status {
+                return Ok(status);
+            }
+        }
+        name.try_into()
+    }
+
+    /// Determines the bits per value of a heightmap from its length.
+    /// Since 1.17 the width depends on the world height, so it can't be fixed.
+    /// Aligned widths resulting in the same length (11 and 12 bits) can't be told apart, the smaller one is assumed.
+    pub(crate) fn get_heightmap_bits(&self, length: usize) -> u8 {
+        match self.packing {
+            Packing::Spanning => (length * 64 / 256) as u8,
+            Packing::Aligned => (1..=32u8)
+                .find(|bits| 256usize.div_ceil(64 / *bits as usize) == length)
+                .unwrap_or(9)
+        }
+    }
+}
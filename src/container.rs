@@ -0,0 +1,122 @@
+use std::borrow::Cow;
+
+use crab_nbt::Nbt;
+
+use crate::{decode_payload, decompress_payload};
+use crate::chunks::{split_payload, CompressionFormat};
+use crate::error::ChunkLoadError;
+use crate::external::{ExternalChunkResolver, EXTERNAL_FLAG};
+
+/// Data stored in the slots of an Anvil container.
+/// The game uses the same container for terrain (`region/`), entities (`entities/`)
+/// and points of interest (`poi/`), so the readers can load any type implementing this.
+///
+/// Implement this for your own type to read third-party data stored in Anvil containers,
+/// or use [`Nbt`] to get the decompressed NBT without any interpretation.
+pub trait ChunkData: Sized {
+    /// Parses the decompressed NBT of a single slot.
+    fn from_nbt(nbt: Nbt) -> Result<Self, ChunkLoadError>;
+}
+impl ChunkData for Nbt {
+    fn from_nbt(nbt: Nbt) -> Result<Self, ChunkLoadError> {
+        Ok(nbt)
+    }
+}
+
+/// The sectors of a single slot, read from the container but not decoded yet.
+/// All readers return this from `read_slot`, their other methods for loading data delegate to it.
+pub struct Slot<'a> {
+    payload: Cow<'a, [u8]>,
+    external: Option<&'a dyn ExternalChunkResolver>,
+    chunk_x: u8,
+    chunk_z: u8
+}
+impl<'a> Slot<'a> {
+    pub(crate) fn new(
+        payload: Cow<'a, [u8]>, external: Option<&'a dyn ExternalChunkResolver>, chunk_x: u8, chunk_z: u8
+    ) -> Self {
+        Slot { payload, external, chunk_x, chunk_z }
+    }
+
+    /// Returns all sectors occupied by the slot, including the length prefix, the compression byte and any padding.
+    pub fn get_payload(&self) -> &[u8] {
+        &self.payload
+    }
+
+    /// Decompresses and parses the data as any type stored in Anvil containers,
+    /// e.g. [`crate::chunks::Chunk`], [`crate::entities::EntityChunk`], [`crate::poi::PoiChunk`] or the raw [`Nbt`].
+    pub fn decode<T: ChunkData>(&self) -> Result<T, ChunkLoadError> {
+        decode_payload(&self.payload, self.external, self.chunk_x, self.chunk_z)
+    }
+
+    /// Returns the decompressed data without parsing it.
+    /// Uncompressed data borrowed from the reader is not copied.
+    pub fn decompress(self) -> Result<Cow<'a, [u8]>, ChunkLoadError> {
+        match self.payload {
+            Cow::Borrowed(payload) => decompress_payload(payload, self.external, self.chunk_x, self.chunk_z),
+            Cow::Owned(payload) => decompress_payload(&payload, self.external, self.chunk_x, self.chunk_z)
+                .map(|data| Cow::Owned(data.into_owned()))
+        }
+    }
+
+    /// Returns the compressed data, resolving external chunks.
+    pub fn into_raw(self) -> Result<RawChunk, ChunkLoadError> {
+        RawChunk::from_payload(&self.payload, self.external, self.chunk_x, self.chunk_z)
+    }
+}
+
+/// The compressed data of a slot, exactly as it is stored in the container.
+/// Moving chunks between region files this way avoids decompressing and recompressing them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawChunk {
+    /// The compression byte, without the flag marking external chunks.
+    /// Kept as a byte, so that unknown formats can still be copied.
+    pub compression: u8,
+    pub data: Vec<u8>
+}
+impl RawChunk {
+    /// Serialises and compresses the NBT.
+    pub fn encode(nbt: &Nbt, compression: CompressionFormat) -> std::io::Result<Self> {
+        Ok(RawChunk { compression: compression as u8, data: compression.compress(&nbt.write())? })
+    }
+
+    /// Parses the payload stored in the region file, loading the data of external chunks.
+    pub(crate) fn from_payload(
+        buf: &[u8], external: Option<&dyn ExternalChunkResolver>, chunk_x: u8, chunk_z: u8
+    ) -> Result<Self, ChunkLoadError> {
+        let (compression, compressed) = split_payload(buf)?;
+        if compression & EXTERNAL_FLAG == 0 {
+            return Ok(RawChunk { compression, data: compressed.to_vec() });
+        }
+        Ok(RawChunk {
+            compression: compression & !EXTERNAL_FLAG,
+            data: external.ok_or(ChunkLoadError::ExternalChunkUnavailable)?
+                .read_external(chunk_x, chunk_z)?
+        })
+    }
+
+    /// Returns the payload format used within region sectors,
+    /// i.e. the data prefixed by its length (including the compression byte) and the compression byte.
+    pub(crate) fn to_payload(&self) -> std::io::Result<Vec<u8>> {
+        if self.compression & EXTERNAL_FLAG != 0 {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "Compression byte contains the external flag"));
+        }
+        let mut buf = Vec::with_capacity(self.data.len() + 5);
+        buf.extend_from_slice(&(self.data.len() as u32 + 1).to_be_bytes());
+        buf.push(self.compression);
+        buf.extend_from_slice(&self.data);
+        Ok(buf)
+    }
+
+    /// Returns None, if the compression format is not supported.
+    pub fn get_compression_format(&self) -> Option<CompressionFormat> {
+        CompressionFormat::try_from(self.compression).ok()
+    }
+
+    /// Decompresses and parses the data.
+    pub fn decode<T: ChunkData>(&self) -> Result<T, ChunkLoadError> {
+        let format = self.get_compression_format()
+            .ok_or(ChunkLoadError::UnknownCompressionFormat(self.compression))?;
+        T::from_nbt(Nbt::read(&mut &format.decompress(&self.data)?[..])?)
+    }
+}
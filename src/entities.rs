@@ -0,0 +1,118 @@
+use crab_nbt::{Nbt, NbtCompound, NbtTag};
+
+use crate::chunks::version::DataVersion;
+use crate::container::ChunkData;
+use crate::error::{malformed_chunk_str, ChunkLoadError};
+
+const POSITION_KEY: &str = "Position";
+const ENTITIES_KEY: &str = "Entities";
+
+/// A chunk from an entity region file, stored in the `entities` directory since 1.17.
+#[derive(Debug)]
+pub struct EntityChunk {
+    pub data_version: Option<DataVersion>,
+    pub data: Nbt
+}
+impl ChunkData for EntityChunk {
+    fn from_nbt(nbt: Nbt) -> Result<Self, ChunkLoadError> {
+        let _ = nbt.get_int_array(POSITION_KEY)
+            .filter(|position| position.len() == 2)
+            .ok_or_else(malformed_chunk_str("Entity chunk has no position"))?;
+        let _ = nbt.get_list(ENTITIES_KEY)
+            .ok_or_else(malformed_chunk_str("Entity chunk has no entities list"))?;
+        Ok(EntityChunk {
+            data_version: nbt.get_int("DataVersion").map(DataVersion),
+            data: nbt
+        })
+    }
+}
+impl EntityChunk {
+    /// Returns the x and z coordinates of this chunk in chunk coordinates (i.e. block coordinates / 16).
+    pub fn get_position(&self) -> [i32; 2] {
+        let position = self.data.get_int_array(POSITION_KEY).unwrap(); // checked in from_nbt
+        [position[0], position[1]]
+    }
+
+    /// Iterates over the entities in this chunk.
+    /// Passengers are not included, they are stored within the entity they are riding.
+    pub fn get_entities(&self) -> EntityIterator<'_> {
+        EntityIterator {
+            tags: self.data.get_list(ENTITIES_KEY).unwrap().iter() // checked in from_nbt
+        }
+    }
+}
+
+/// An entity, e.g. a mob, item or minecart.
+#[derive(Debug, Clone, Copy)]
+pub struct Entity<'a> {
+    /// The namespaced id of the entity type
+    pub id: &'a String,
+    pub uuid: u128,
+    /// The absolute world coordinates of the entity
+    pub position: [f64; 3],
+    /// The velocity in blocks per tick
+    pub motion: [f64; 3],
+    /// The yaw and pitch in degrees
+    pub rotation: [f32; 2],
+    /// The custom name as a text component.
+    /// It is stored as a JSON string before 1.21.5 and as NBT since.
+    pub custom_name: Option<&'a NbtTag>,
+    /// The complete NBT of the entity
+    pub nbt: &'a NbtCompound
+}
+impl<'a> Entity<'a> {
+    pub(crate) fn new(compound: &'a NbtCompound) -> Result<Self, ChunkLoadError> {
+        let uuid = match compound.get_int_array("UUID").map(Vec::as_slice) {
+            Some(&[a, b, c, d]) => [a, b, c, d].iter()
+                .fold(0u128, |uuid, part| uuid << 32 | *part as u32 as u128),
+            _ => return Err(ChunkLoadError::MalformedChunk("Entity has no UUID".to_owned()))
+        };
+        Ok(Entity {
+            id: compound.get_string("id").ok_or_else(malformed_chunk_str("Entity has no id"))?,
+            uuid,
+            position: read_list(compound, "Pos", NbtTag::extract_double)?
+                .ok_or_else(malformed_chunk_str("Entity has no position"))?,
+            motion: read_list(compound, "Motion", NbtTag::extract_double)?.unwrap_or_default(),
+            rotation: read_list(compound, "Rotation", NbtTag::extract_float)?.unwrap_or_default(),
+            custom_name: compound.get("CustomName"),
+            nbt: compound
+        })
+    }
+
+    /// Returns the UUID in its usual hyphenated form.
+    pub fn get_uuid_string(&self) -> String {
+        let hex = format!("{:032x}", self.uuid);
+        format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+    }
+}
+
+/// Reads a list with a fixed number of elements, e.g. a position.
+/// Returns None, if the list does not exist.
+fn read_list<T: Default + Copy, const N: usize>(
+    compound: &NbtCompound, key: &str, extract: impl Fn(&NbtTag) -> Option<T>
+) -> Result<Option<[T; N]>, ChunkLoadError> {
+    let Some(list) = compound.get_list(key) else {
+        return Ok(None);
+    };
+    if list.len() != N {
+        return Err(ChunkLoadError::MalformedChunk(format!("Entity {key} should have {N} elements")));
+    }
+    let mut values = [T::default(); N];
+    for (value, tag) in values.iter_mut().zip(list) {
+        *value = extract(tag).ok_or_else(|| ChunkLoadError::MalformedChunk(format!("Entity {key} has the wrong type")))?;
+    }
+    Ok(Some(values))
+}
+
+pub struct EntityIterator<'a> {
+    tags: std::slice::Iter<'a, NbtTag>
+}
+impl<'a> Iterator for EntityIterator<'a> {
+    type Item = Result<Entity<'a>, ChunkLoadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.tags.next().map(|tag| tag.extract_compound()
+            .ok_or_else(malformed_chunk_str("Entity is not a compound"))
+            .and_then(Entity::new))
+    }
+}
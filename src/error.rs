@@ -6,9 +6,13 @@ pub enum ChunkLoadError {
     EmptySection,
     MalformedChunk(String),
     ChunkDoesNotExist,
+    /// The chunk is stored in an external file, but no resolver is available to locate it
+    ExternalChunkUnavailable,
     IOError(std::io::Error),
     UnknownCompressionFormat(u8),
     MalformedNbt(crab_nbt::error::Error),
+    /// The operation is not supported for the chunk's format, e.g. modifying chunks saved before 1.13
+    UnsupportedFormat,
 }
 impl From<std::io::Error> for ChunkLoadError {
     fn from(value: std::io::Error) -> Self {
@@ -29,4 +33,13 @@ impl Error for ChunkLoadError { }
 
 pub(crate) fn malformed_chunk_str(error: &str) -> impl Fn() -> ChunkLoadError {
     || ChunkLoadError::MalformedChunk(error.to_owned())
-}
\ No newline at end of file
+}
+/// The string is not a block state in the form `namespace:name[property=value,...]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseBlockStateError(pub String);
+impl Display for ParseBlockStateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid block state: {}", self.0)
+    }
+}
+impl Error for ParseBlockStateError { }
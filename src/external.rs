@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::{CHUNKS_PER_AXIS, LOCATION_SIZE_FACTOR};
+
+/// Set on the compression byte if the chunk payload is stored outside of the region file.
+pub(crate) const EXTERNAL_FLAG: u8 = 0b1000_0000;
+
+/// Locates chunk payloads that are too large to be stored within the region file (more than 255 sectors).
+/// The data handled by the resolver is the compressed chunk, without the length and compression header.
+/// Coordinates are relative to the region, like everywhere else in the region APIs.
+pub trait ExternalChunkResolver: Send + Sync {
+    fn read_external(&self, chunk_x: u8, chunk_z: u8) -> std::io::Result<Vec<u8>>;
+
+    fn write_external(&mut self, chunk_x: u8, chunk_z: u8, data: &[u8]) -> std::io::Result<()>;
+
+    /// Removes the external data of the chunk. Removing data that does not exist is not an error.
+    fn remove_external(&mut self, chunk_x: u8, chunk_z: u8) -> std::io::Result<()>;
+}
+
+/// Resolves external chunks the way vanilla does, i.e. as `c.<x>.<z>.mcc` files
+/// in the same directory as the region file, using absolute chunk coordinates.
+#[derive(Debug, Clone)]
+pub struct DirectoryResolver {
+    directory: PathBuf,
+    region_x: i32,
+    region_z: i32
+}
+impl DirectoryResolver {
+    pub fn new(directory: impl Into<PathBuf>, region_x: i32, region_z: i32) -> Self {
+        DirectoryResolver { directory: directory.into(), region_x, region_z }
+    }
+
+    /// Creates a resolver from the path of a region file named `r.<x>.<z>.mca`.
+    /// Returns None, if the file name does not follow that pattern.
+    pub fn for_region_file(path: &Path) -> Option<Self> {
+        let [region_x, region_z] = parse_region_file_name(path.file_name()?.to_str()?)?;
+        let directory = path.parent().unwrap_or(Path::new("."));
+        Some(DirectoryResolver::new(directory, region_x, region_z))
+    }
+
+    pub fn get_path(&self, chunk_x: u8, chunk_z: u8) -> PathBuf {
+        let x = self.region_x * CHUNKS_PER_AXIS as i32 + chunk_x as i32;
+        let z = self.region_z * CHUNKS_PER_AXIS as i32 + chunk_z as i32;
+        self.directory.join(format!("c.{x}.{z}.mcc"))
+    }
+}
+impl ExternalChunkResolver for DirectoryResolver {
+    fn read_external(&self, chunk_x: u8, chunk_z: u8) -> std::io::Result<Vec<u8>> {
+        std::fs::read(self.get_path(chunk_x, chunk_z))
+    }
+
+    fn write_external(&mut self, chunk_x: u8, chunk_z: u8, data: &[u8]) -> std::io::Result<()> {
+        std::fs::write(self.get_path(chunk_x, chunk_z), data)
+    }
+
+    fn remove_external(&mut self, chunk_x: u8, chunk_z: u8) -> std::io::Result<()> {
+        match std::fs::remove_file(self.get_path(chunk_x, chunk_z)) {
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+            result => result
+        }
+    }
+}
+
+/// Keeps external chunks in memory, e.g. for regions that are not backed by a file system.
+/// Clones share the same storage, so a resolver can be handed to a writer and later to a reader.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryResolver {
+    chunks: Arc<Mutex<HashMap<[u8; 2], Vec<u8>>>>
+}
+impl MemoryResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn contains(&self, chunk_x: u8, chunk_z: u8) -> bool {
+        self.chunks.lock().unwrap().contains_key(&[chunk_x, chunk_z])
+    }
+}
+impl ExternalChunkResolver for MemoryResolver {
+    fn read_external(&self, chunk_x: u8, chunk_z: u8) -> std::io::Result<Vec<u8>> {
+        self.chunks.lock().unwrap()
+            .get(&[chunk_x, chunk_z])
+            .cloned()
+            .ok_or_else(|| std::io::Error::new(ErrorKind::NotFound, "External chunk does not exist"))
+    }
+
+    fn write_external(&mut self, chunk_x: u8, chunk_z: u8, data: &[u8]) -> std::io::Result<()> {
+        self.chunks.lock().unwrap().insert([chunk_x, chunk_z], data.to_vec());
+        Ok(())
+    }
+
+    fn remove_external(&mut self, chunk_x: u8, chunk_z: u8) -> std::io::Result<()> {
+        self.chunks.lock().unwrap().remove(&[chunk_x, chunk_z]);
+        Ok(())
+    }
+}
+
+/// Moves the payload to the resolver, if it does not fit into the region file.
+/// Returns the payload that should be written to the region file instead.
+pub(crate) fn externalize_payload(
+    payload: Vec<u8>, external: Option<&mut (dyn ExternalChunkResolver + 'static)>, chunk_x: u8, chunk_z: u8
+) -> std::io::Result<Vec<u8>> {
+    if payload.len() <= u8::MAX as usize * LOCATION_SIZE_FACTOR {
+        return Ok(payload);
+    }
+    let resolver = external.ok_or_else(|| std::io::Error::new(
+        ErrorKind::InvalidInput,
+        "Chunk exceeds the maximum size of 255 sectors and there is no resolver for external chunks"
+    ))?;
+    resolver.write_external(chunk_x, chunk_z, &payload[5..])?;
+    // Only the compression byte remains in the region file
+    Ok(vec![0, 0, 0, 1, payload[4] | EXTERNAL_FLAG])
+}
+
+/// Parses the region coordinates out of a file name like `r.<x>.<z>.mca`.
+/// McRegion files (`.mcr`) are accepted as well.
+pub(crate) fn parse_region_file_name(name: &str) -> Option<[i32; 2]> {
+    let name = name.strip_prefix("r.")?;
+    let mut parts = name.strip_suffix(".mca").or_else(|| name.strip_suffix(".mcr"))?.split('.');
+    let x = parts.next()?.parse().ok()?;
+    let z = parts.next()?.parse().ok()?;
+    if parts.next().is_some() {
+        return None;
+    }
+    Some([x, z])
+}
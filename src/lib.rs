@@ -1,69 +1,200 @@
+use std::borrow::Cow;
 use std::io::{Read, Seek, SeekFrom};
+use std::marker::PhantomData;
+use std::ops::Deref;
 
-use crate::{chunks::Chunk, error::ChunkLoadError, metadata::{ChunkTimestamp, LocationTable, TimestampTable}};
+use crab_nbt::Nbt;
+
+use crate::{chunks::{split_payload, Chunk, CompressionFormat}, error::ChunkLoadError, metadata::{ChunkInfo, LocationTableEntry, RegionHeader}};
+use crate::container::{ChunkData, RawChunk, Slot};
+use crate::entities::EntityChunk;
+use crate::external::{ExternalChunkResolver, EXTERNAL_FLAG};
+use crate::poi::PoiChunk;
 
 pub mod error;
 pub mod chunks;
+pub mod container;
+pub mod entities;
 pub mod metadata;
+pub mod poi;
+pub mod external;
+pub mod world;
+pub mod validation;
+mod writer;
+mod region;
+mod slice;
+#[cfg(feature = "rayon")]
+mod parallel;
+#[cfg(feature = "tokio")]
+mod asynchronous;
+
+pub use writer::RegionFileWriter;
+pub use region::{RegionFile, SetLen};
+pub use slice::SliceRegionReader;
+#[cfg(feature = "tokio")]
+pub use asynchronous::AsyncRegionFileReader;
 
 const TABLE_SIZE: usize = 1024;
 const ENTRY_SIZE: usize = 4;
 const LOCATION_SIZE_FACTOR: usize = 4096;
 const CHUNKS_PER_AXIS: u8 = 32;
+// The location and timestamp tables occupy the first two sectors of every region file
+const HEADER_SECTORS: u32 = 2;
 
 pub struct RegionFileReader<R: Read + Seek> {
     reader: R,
-    location_table: LocationTable,
-    timestamp_table: TimestampTable
+    header: RegionHeader,
+    external: Option<Box<dyn ExternalChunkResolver>>
 }
 impl<R: Read + Seek> RegionFileReader<R> {
     pub fn create(mut reader: R) -> std::io::Result<Self> {
         reader.seek(SeekFrom::Start(0))?;
         Ok(RegionFileReader {
-            location_table: LocationTable::read(&mut reader)?,
-            timestamp_table: TimestampTable::read(&mut reader)?,
-            reader: reader // order is weird because of mutable borrows above
+            header: RegionHeader::read(&mut reader)?,
+            reader: reader, // order is weird because of mutable borrows above
+            external: None
         })
     }
 
-    pub fn get_timestamps(&self) -> &TimestampTable {
-        &self.timestamp_table
+    /// Sets the resolver used to load chunks that are stored outside of the region file.
+    /// Without a resolver these chunks fail to load with [`ChunkLoadError::ExternalChunkUnavailable`].
+    pub fn with_external_resolver(mut self, resolver: impl ExternalChunkResolver + 'static) -> Self {
+        self.external = Some(Box::new(resolver));
+        self
     }
 
-    pub fn get_timestamp(&self, chunk_x: u8, chunk_z: u8) -> Option<ChunkTimestamp> {
-        self.get_timestamps().as_ref()
-            .get(get_chunk_index(chunk_x, chunk_z)).copied()
+    /// Reads the location and payload header of the chunk, without loading the chunk itself.
+    /// Returns None, if the chunk does not exist.
+    /// Fails with [`std::io::ErrorKind::InvalidInput`], if the coordinates are not in [0;32).
+    pub fn get_chunk_info(&mut self, chunk_x: u8, chunk_z: u8) -> std::io::Result<Option<ChunkInfo>> {
+        read_chunk_info(&mut self.reader, self.header.get_location(chunk_x, chunk_z)?)
+    }
+
+    /// Reads the sectors of the slot without decoding them.
+    pub fn read_slot(&mut self, chunk_x: u8, chunk_z: u8) -> Result<Slot<'_>, ChunkLoadError> {
+        let payload = read_payload(&mut self.reader, self.header.get_location(chunk_x, chunk_z)?)?;
+        Ok(Slot::new(Cow::Owned(payload), self.external.as_deref(), chunk_x, chunk_z))
     }
 
     pub fn get_chunk(&mut self, chunk_x: u8, chunk_z: u8) -> Result<Chunk, ChunkLoadError> {
-        let location = &self.location_table[get_chunk_index(chunk_x, chunk_z)];
-        
-        let (seek, size) = location.to_offset_form();
-        if size == 0 {
-            return Err(ChunkLoadError::ChunkDoesNotExist)
-        }
-        self.reader.seek(SeekFrom::Start(seek))?;
-        let mut buf = vec![0u8; size];
-        self.reader.read_exact(&mut buf)?;
+        self.get_chunk_as(chunk_x, chunk_z)
+    }
+
+    /// Loads the data in the specified slot as any type stored in Anvil containers, see [`Slot::decode`].
+    pub fn get_chunk_as<T: ChunkData>(&mut self, chunk_x: u8, chunk_z: u8) -> Result<T, ChunkLoadError> {
+        self.read_slot(chunk_x, chunk_z)?.decode()
+    }
+
+    /// See [`Slot::decompress`].
+    pub fn get_chunk_bytes(&mut self, chunk_x: u8, chunk_z: u8) -> Result<Vec<u8>, ChunkLoadError> {
+        self.read_slot(chunk_x, chunk_z)?.decompress().map(Cow::into_owned)
+    }
+
+    /// See [`Slot::into_raw`].
+    /// Use [`RegionFileWriter::write_raw_chunk`] to copy it into another region file.
+    pub fn get_raw_chunk(&mut self, chunk_x: u8, chunk_z: u8) -> Result<RawChunk, ChunkLoadError> {
+        self.read_slot(chunk_x, chunk_z)?.into_raw()
+    }
+
+    /// Loads the chunk at the specified position from an entity region file (`entities/`).
+    pub fn get_entity_chunk(&mut self, chunk_x: u8, chunk_z: u8) -> Result<EntityChunk, ChunkLoadError> {
+        self.get_chunk_as(chunk_x, chunk_z)
+    }
 
-        Ok(Chunk::read(&buf)?)
+    /// Loads the chunk at the specified position from a point of interest region file (`poi/`).
+    pub fn get_poi_chunk(&mut self, chunk_x: u8, chunk_z: u8) -> Result<PoiChunk, ChunkLoadError> {
+        self.get_chunk_as(chunk_x, chunk_z)
     }
 
-    pub fn get_chunks(&mut self) -> impl Iterator<Item = ([u8; 2], Option<Result<Chunk, ChunkLoadError>>)> {
-        ChunkIterator { x: 0, z: 0, reader: self }
+    pub fn get_chunks(&mut self) -> ChunkIterator<'_, R, Chunk> {
+        self.get_chunks_as()
+    }
+
+    /// Iterates over all slots, loading the existing ones as any type stored in Anvil containers.
+    pub fn get_chunks_as<T: ChunkData>(&mut self) -> ChunkIterator<'_, R, T> {
+        ChunkIterator { x: 0, z: 0, reader: self, data: PhantomData }
     }
 }
+impl<R: Read + Seek> Deref for RegionFileReader<R> {
+    type Target = RegionHeader;
 
-fn get_chunk_index(chunk_x: u8, chunk_z: u8) -> usize {
+    fn deref(&self) -> &Self::Target {
+        &self.header
+    }
+}
+
+pub(crate) fn read_chunk_info<R: Read + Seek>(reader: &mut R, location: &LocationTableEntry) -> std::io::Result<Option<ChunkInfo>> {
+    if location.is_empty() {
+        return Ok(None);
+    }
+    let (seek, _) = location.to_offset_form();
+    let mut header = [0u8; 5];
+    reader.seek(SeekFrom::Start(seek))?;
+    reader.read_exact(&mut header)?;
+    Ok(Some(ChunkInfo::new(location.clone(), header)))
+}
+
+/// Reads all sectors occupied by the chunk, without decoding them.
+pub(crate) fn read_payload<R: Read + Seek>(reader: &mut R, location: &LocationTableEntry) -> Result<Vec<u8>, ChunkLoadError> {
+    let (seek, size) = location.to_offset_form();
+    if size == 0 {
+        return Err(ChunkLoadError::ChunkDoesNotExist)
+    }
+    reader.seek(SeekFrom::Start(seek))?;
+    let mut buf = vec![0u8; size];
+    reader.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+/// Decodes the data from its payload within the region file, resolving external data if necessary.
+pub(crate) fn decode_payload<T: ChunkData>(
+    buf: &[u8], external: Option<&dyn ExternalChunkResolver>, chunk_x: u8, chunk_z: u8
+) -> Result<T, ChunkLoadError> {
+    let decompressed = decompress_payload(buf, external, chunk_x, chunk_z)?;
+    T::from_nbt(Nbt::read(&mut &decompressed[..])?)
+}
+
+/// Decompresses the data stored in a payload within the region file, resolving external data if necessary.
+pub(crate) fn decompress_payload<'a>(
+    buf: &'a [u8], external: Option<&dyn ExternalChunkResolver>, chunk_x: u8, chunk_z: u8
+) -> Result<Cow<'a, [u8]>, ChunkLoadError> {
+    let (compression, compressed) = split_payload(buf)?;
+    if compression & EXTERNAL_FLAG == 0 {
+        return decompress(compression, compressed);
+    }
+    let data = external.ok_or(ChunkLoadError::ExternalChunkUnavailable)?
+        .read_external(chunk_x, chunk_z)?;
+    Ok(Cow::Owned(decompress(compression & !EXTERNAL_FLAG, &data)?.into_owned()))
+}
+
+fn decompress(compression: u8, compressed: &[u8]) -> Result<Cow<'_, [u8]>, ChunkLoadError> {
+    let format = CompressionFormat::try_from(compression)
+        .map_err(|_| ChunkLoadError::UnknownCompressionFormat(compression))?;
+    Ok(format.decompress(compressed)?)
+}
+
+pub(crate) fn get_chunk_index(chunk_x: u8, chunk_z: u8) -> usize {
     chunk_z as usize * 32 + chunk_x as usize
 }
 
-pub struct ChunkIterator<'a, R: Read + Seek> {
+/// Rejects coordinates outside of the region, which would otherwise alias another slot.
+pub(crate) fn check_chunk_coordinates(chunk_x: u8, chunk_z: u8) -> std::io::Result<()> {
+    if chunk_x >= CHUNKS_PER_AXIS || chunk_z >= CHUNKS_PER_AXIS {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("components of ({chunk_x},{chunk_z}) are not in [0;32)")
+        ));
+    }
+    Ok(())
+}
+
+pub struct ChunkIterator<'a, R: Read + Seek, T: ChunkData = Chunk> {
     x: u8, z: u8,
-    reader: &'a mut RegionFileReader<R>
+    reader: &'a mut RegionFileReader<R>,
+    data: PhantomData<T>
 }
-impl<'a, R: Read + Seek> Iterator for ChunkIterator<'a, R> {
-    type Item = ([u8; 2], Option<Result<Chunk, ChunkLoadError>>);
+impl<'a, R: Read + Seek, T: ChunkData> Iterator for ChunkIterator<'a, R, T> {
+    type Item = ([u8; 2], Option<Result<T, ChunkLoadError>>);
 
     fn next(&mut self) -> Option<Self::Item> {
         let result = if self.z >= CHUNKS_PER_AXIS {
@@ -74,7 +205,7 @@ impl<'a, R: Read + Seek> Iterator for ChunkIterator<'a, R> {
                 if self.reader.get_timestamp(self.x, self.z).unwrap() == 0 {
                     None
                 } else {
-                    Some(self.reader.get_chunk(self.x, self.z))
+                    Some(self.reader.get_chunk_as(self.x, self.z))
                 }
             ))
         };
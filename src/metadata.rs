@@ -1,15 +1,25 @@
-use std::{io::Read, ops::{Deref, Index}};
+use std::{io::{Read, Write}, ops::{Deref, Index, IndexMut}};
 
-use crate::{ENTRY_SIZE, LOCATION_SIZE_FACTOR, TABLE_SIZE};
+#[cfg(feature = "tokio")]
+use tokio::io::{AsyncRead, AsyncReadExt};
 
-#[derive(Debug)]
-pub(crate) struct LocationTableEntry {
+use crate::{CHUNKS_PER_AXIS, ENTRY_SIZE, LOCATION_SIZE_FACTOR, TABLE_SIZE};
+use crate::chunks::CompressionFormat;
+use crate::external::EXTERNAL_FLAG;
+
+/// Where a chunk is stored within the region file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocationTableEntry {
     // position within the file (starting at 0) in 4096 bytes
     position: u32,
     // size in 4096 bytes
     size: u8
 }
 impl LocationTableEntry {
+    pub(crate) fn new(position: u32, size: u8) -> Self {
+        LocationTableEntry { position, size }
+    }
+
     fn from_bytes(bytes: &[u8]) -> Self {
         LocationTableEntry {
             position: u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]),
@@ -17,10 +27,30 @@ impl LocationTableEntry {
         }
     }
 
+    pub(crate) fn to_bytes(&self) -> [u8; 4] {
+        let position = self.position.to_be_bytes();
+        [position[1], position[2], position[3], self.size]
+    }
+
     pub fn is_empty(&self) -> bool {
         self.position == 0 && self.size == 0
     }
 
+    /// Returns the offset of the chunk within the file in sectors of 4096 bytes
+    pub fn get_sector_offset(&self) -> u32 {
+        self.position
+    }
+
+    /// Returns the number of 4096 byte sectors allocated for the chunk
+    pub fn get_sector_count(&self) -> u8 {
+        self.size
+    }
+
+    /// Returns the range of sectors occupied by this entry
+    pub fn sectors(&self) -> std::ops::Range<u32> {
+        self.position..self.position + self.size as u32
+    }
+
     /// Converts this entry into the offset form.
     /// This means the first value is the offset within the file in bytes,
     /// and the second is the size of the chunk in bytes (rounded up to the nearest factor of 4096)
@@ -32,14 +62,26 @@ impl LocationTableEntry {
     }
 }
 
-pub(crate) struct LocationTable {
+/// The first sector of a region file, describing where each chunk is stored.
+/// Entries are ordered by z first, then x, like everywhere else in a region.
+pub struct LocationTable {
     internal: Vec<LocationTableEntry>
 }
 impl LocationTable {
     pub(crate) fn read<R: Read>(reader: &mut R) -> std::io::Result<Self> {
         let mut buf = [0u8; TABLE_SIZE * ENTRY_SIZE];
         reader.read_exact(&mut buf)?;
+        Ok(Self::from_bytes(&buf))
+    }
 
+    #[cfg(feature = "tokio")]
+    pub(crate) async fn read_async<R: AsyncRead + Unpin>(reader: &mut R) -> std::io::Result<Self> {
+        let mut buf = [0u8; TABLE_SIZE * ENTRY_SIZE];
+        reader.read_exact(&mut buf).await?;
+        Ok(Self::from_bytes(&buf))
+    }
+
+    fn from_bytes(buf: &[u8; TABLE_SIZE * ENTRY_SIZE]) -> Self {
         // Not all that happy with using a Vec here (array could work to, because constant-tiem size)
         // but I can't really work safely with uninitialised arrays in Rust :(
         let mut location_table = Vec::with_capacity(TABLE_SIZE);
@@ -47,7 +89,39 @@ impl LocationTable {
             let pos = i * 4;
             location_table.push(LocationTableEntry::from_bytes(&buf[pos..pos+4]))
         }
-        Ok(LocationTable { internal: location_table })
+        LocationTable { internal: location_table }
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, LocationTableEntry> {
+        self.internal.iter()
+    }
+
+    /// Returns None, if the coordinates are not in [0;32).
+    pub fn get(&self, chunk_x: u8, chunk_z: u8) -> Option<&LocationTableEntry> {
+        crate::check_chunk_coordinates(chunk_x, chunk_z).ok()?;
+        Some(&self.internal[crate::get_chunk_index(chunk_x, chunk_z)])
+    }
+
+    /// Iterates over the coordinates of all chunks that have a location within the file.
+    pub fn present_chunks(&self) -> impl Iterator<Item = [u8; 2]> + '_ {
+        self.internal.iter().enumerate()
+            .filter(|(_, entry)| !entry.is_empty())
+            .map(|(i, _)| [(i % CHUNKS_PER_AXIS as usize) as u8, (i / CHUNKS_PER_AXIS as usize) as u8])
+    }
+
+    pub(crate) fn empty() -> Self {
+        LocationTable {
+            internal: (0..TABLE_SIZE).map(|_| LocationTableEntry::new(0, 0)).collect()
+        }
+    }
+
+    pub(crate) fn write<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        let mut buf = [0u8; TABLE_SIZE * ENTRY_SIZE];
+        for (i, entry) in self.internal.iter().enumerate() {
+            let pos = i * 4;
+            buf[pos..pos+4].copy_from_slice(&entry.to_bytes());
+        }
+        writer.write_all(&buf)
     }
 }
 impl Index<usize> for LocationTable {
@@ -57,6 +131,40 @@ impl Index<usize> for LocationTable {
         &self.internal[index]
     }
 }
+impl IndexMut<usize> for LocationTable {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.internal[index]
+    }
+}
+
+/// Details about a stored chunk, read from its location entry and the header of its payload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkInfo {
+    pub location: LocationTableEntry,
+    /// Length of the payload in bytes, including the compression byte
+    pub length: u32,
+    /// The raw compression byte, which may have the external flag set
+    pub compression: u8
+}
+impl ChunkInfo {
+    pub(crate) fn new(location: LocationTableEntry, header: [u8; 5]) -> Self {
+        ChunkInfo {
+            location,
+            length: u32::from_be_bytes([header[0], header[1], header[2], header[3]]),
+            compression: header[4]
+        }
+    }
+
+    /// Returns the compression format, if it is known.
+    pub fn get_compression_format(&self) -> Option<CompressionFormat> {
+        CompressionFormat::try_from(self.compression & !EXTERNAL_FLAG).ok()
+    }
+
+    /// Checks whether the chunk data is stored in an external file.
+    pub fn is_external(&self) -> bool {
+        self.compression & EXTERNAL_FLAG != 0
+    }
+}
 
 pub type ChunkTimestamp = i32;
 pub struct TimestampTable {
@@ -66,14 +174,37 @@ impl TimestampTable {
     pub(crate) fn read<R: Read>(reader: &mut R) -> std::io::Result<Self> {
         let mut buf = [0u8; TABLE_SIZE * ENTRY_SIZE];
         reader.read_exact(&mut buf)?;
+        Ok(Self::from_bytes(&buf))
+    }
 
+    #[cfg(feature = "tokio")]
+    pub(crate) async fn read_async<R: AsyncRead + Unpin>(reader: &mut R) -> std::io::Result<Self> {
+        let mut buf = [0u8; TABLE_SIZE * ENTRY_SIZE];
+        reader.read_exact(&mut buf).await?;
+        Ok(Self::from_bytes(&buf))
+    }
+
+    fn from_bytes(buf: &[u8; TABLE_SIZE * ENTRY_SIZE]) -> Self {
         let mut timestamp_table = Vec::with_capacity(TABLE_SIZE);
         for i in 0..TABLE_SIZE {
             let pos = i * 4;
             timestamp_table.push(ChunkTimestamp::from_be_bytes(buf[pos..pos+4].try_into()
                 .expect("Slice has unexpected length. This should never happen")));
         }
-        Ok(TimestampTable { internal: timestamp_table })
+        TimestampTable { internal: timestamp_table }
+    }
+
+    pub(crate) fn empty() -> Self {
+        TimestampTable { internal: vec![0; TABLE_SIZE] }
+    }
+
+    pub(crate) fn write<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        let mut buf = [0u8; TABLE_SIZE * ENTRY_SIZE];
+        for (i, timestamp) in self.internal.iter().enumerate() {
+            let pos = i * 4;
+            buf[pos..pos+4].copy_from_slice(&timestamp.to_be_bytes());
+        }
+        writer.write_all(&buf)
     }
 }
 impl Index<usize> for TimestampTable {
@@ -83,6 +214,11 @@ impl Index<usize> for TimestampTable {
         &self.internal[index]
     }
 }
+impl IndexMut<usize> for TimestampTable {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.internal[index]
+    }
+}
 impl From<TimestampTable> for Vec<ChunkTimestamp> {
     fn from(value: TimestampTable) -> Self {
         value.internal
@@ -99,4 +235,55 @@ impl Deref for TimestampTable {
     fn deref(&self) -> &Self::Target {
         &self.internal
     }
-}
\ No newline at end of file
+}
+/// The first two sectors of a region file: where each chunk is stored and when it was last saved.
+/// Every reader dereferences to the header of its file.
+pub struct RegionHeader {
+    pub(crate) location_table: LocationTable,
+    pub(crate) timestamp_table: TimestampTable
+}
+impl RegionHeader {
+    pub(crate) fn read<R: Read>(reader: &mut R) -> std::io::Result<Self> {
+        Ok(RegionHeader {
+            location_table: LocationTable::read(reader)?,
+            timestamp_table: TimestampTable::read(reader)?
+        })
+    }
+
+    #[cfg(feature = "tokio")]
+    pub(crate) async fn read_async<R: AsyncRead + Unpin>(reader: &mut R) -> std::io::Result<Self> {
+        Ok(RegionHeader {
+            location_table: LocationTable::read_async(reader).await?,
+            timestamp_table: TimestampTable::read_async(reader).await?
+        })
+    }
+
+    pub(crate) fn empty() -> Self {
+        RegionHeader { location_table: LocationTable::empty(), timestamp_table: TimestampTable::empty() }
+    }
+
+    pub(crate) fn write<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        self.location_table.write(writer)?;
+        self.timestamp_table.write(writer)
+    }
+
+    pub fn get_timestamps(&self) -> &TimestampTable {
+        &self.timestamp_table
+    }
+
+    /// Returns None, if the coordinates are not in [0;32).
+    pub fn get_timestamp(&self, chunk_x: u8, chunk_z: u8) -> Option<ChunkTimestamp> {
+        crate::check_chunk_coordinates(chunk_x, chunk_z).ok()?;
+        Some(self.timestamp_table[crate::get_chunk_index(chunk_x, chunk_z)])
+    }
+
+    pub fn get_location_table(&self) -> &LocationTable {
+        &self.location_table
+    }
+
+    /// Returns the location entry of the slot, failing if the coordinates are not in [0;32).
+    pub(crate) fn get_location(&self, chunk_x: u8, chunk_z: u8) -> std::io::Result<&LocationTableEntry> {
+        crate::check_chunk_coordinates(chunk_x, chunk_z)?;
+        Ok(&self.location_table[crate::get_chunk_index(chunk_x, chunk_z)])
+    }
+}
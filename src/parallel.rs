@@ -0,0 +1,30 @@
+use std::io::{Read, Seek};
+
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::{decode_payload, get_chunk_index, read_payload, RegionFileReader, CHUNKS_PER_AXIS};
+use crate::chunks::Chunk;
+use crate::error::ChunkLoadError;
+
+impl<R: Read + Seek> RegionFileReader<R> {
+    /// Reads the payloads of all existing chunks and decodes them on the rayon thread pool.
+    /// Reading from the underlying reader happens up front and sequentially,
+    /// only decompression and parsing are parallelised.
+    /// The order of the results is unspecified.
+    pub fn par_get_chunks(&mut self) -> impl ParallelIterator<Item = ([u8; 2], Result<Chunk, ChunkLoadError>)> + '_ {
+        let mut payloads = Vec::new();
+        for z in 0..CHUNKS_PER_AXIS {
+            for x in 0..CHUNKS_PER_AXIS {
+                let location = &self.header.location_table[get_chunk_index(x, z)];
+                if !location.is_empty() {
+                    payloads.push(([x, z], read_payload(&mut self.reader, location)));
+                }
+            }
+        }
+
+        let external = self.external.as_deref();
+        payloads.into_par_iter().map(move |([x, z], payload)| {
+            ([x, z], payload.and_then(|payload| decode_payload(&payload, external, x, z)))
+        })
+    }
+}
@@ -0,0 +1,102 @@
+use crab_nbt::{Nbt, NbtCompound, NbtTag};
+
+use crate::chunks::version::DataVersion;
+use crate::container::ChunkData;
+use crate::error::{malformed_chunk_str, ChunkLoadError};
+
+const SECTIONS_KEY: &str = "Sections";
+
+/// A chunk from a point of interest region file, stored in the `poi` directory since 1.14.
+#[derive(Debug)]
+pub struct PoiChunk {
+    pub data_version: Option<DataVersion>,
+    pub data: Nbt
+}
+impl ChunkData for PoiChunk {
+    fn from_nbt(nbt: Nbt) -> Result<Self, ChunkLoadError> {
+        let _ = nbt.get_compound(SECTIONS_KEY)
+            .ok_or_else(malformed_chunk_str("POI chunk has no sections"))?;
+        Ok(PoiChunk {
+            data_version: nbt.get_int("DataVersion").map(DataVersion),
+            data: nbt
+        })
+    }
+}
+impl PoiChunk {
+    /// Iterates over the sections containing points of interest, in the order they are stored.
+    pub fn get_sections(&self) -> impl Iterator<Item = Result<PoiSection<'_>, ChunkLoadError>> {
+        self.data.get_compound(SECTIONS_KEY).unwrap() // checked in from_nbt
+            .child_tags.iter()
+            .map(|(key, tag)| PoiSection::new(key, tag))
+    }
+
+    /// Iterates over the records of all sections.
+    pub fn get_records(&self) -> impl Iterator<Item = Result<PoiRecord<'_>, ChunkLoadError>> {
+        self.get_sections().flat_map(|section| -> Box<dyn Iterator<Item = _>> {
+            match section {
+                Ok(section) => Box::new(section.get_records()),
+                Err(e) => Box::new(std::iter::once(Err(e)))
+            }
+        })
+    }
+}
+
+/// The points of interest within a 16x16x16 section.
+#[derive(Debug, Clone, Copy)]
+pub struct PoiSection<'a> {
+    /// The section y coordinate (i.e. block y / 16)
+    pub y: i32,
+    /// Whether the records are up to date. Invalid sections are rebuilt by the game when they are loaded.
+    pub valid: bool,
+    records: &'a Vec<NbtTag>
+}
+impl<'a> PoiSection<'a> {
+    fn new(key: &str, tag: &'a NbtTag) -> Result<Self, ChunkLoadError> {
+        let compound = tag.extract_compound()
+            .ok_or_else(malformed_chunk_str("POI section is not a compound"))?;
+        Ok(PoiSection {
+            y: key.parse().map_err(|_| ChunkLoadError::MalformedChunk(format!("POI section key {key} is not a number")))?,
+            valid: compound.get_bool("Valid").unwrap_or(true),
+            records: compound.get_list("Records")
+                .ok_or_else(malformed_chunk_str("POI section has no records"))?
+        })
+    }
+
+    pub fn get_records(&self) -> impl Iterator<Item = Result<PoiRecord<'a>, ChunkLoadError>> + 'a {
+        self.records.iter().map(|tag| tag.extract_compound()
+            .ok_or_else(malformed_chunk_str("POI record is not a compound"))
+            .and_then(PoiRecord::new))
+    }
+}
+
+/// A point of interest, e.g. a workstation, bed, bell or nether portal block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoiRecord<'a> {
+    /// The namespaced id of the POI type, e.g. `minecraft:nether_portal`
+    pub poi_type: &'a String,
+    /// The absolute world coordinates of the block
+    pub position: [i32; 3],
+    /// The number of villagers that can still claim this point of interest
+    pub free_tickets: i32
+}
+impl<'a> PoiRecord<'a> {
+    fn new(compound: &'a NbtCompound) -> Result<Self, ChunkLoadError> {
+        Ok(PoiRecord {
+            poi_type: compound.get_string("type").ok_or_else(malformed_chunk_str("POI record has no type"))?,
+            position: read_position(compound)?,
+            free_tickets: compound.get_int("free_tickets").unwrap_or(0)
+        })
+    }
+}
+
+/// Reads the position, stored as an int array since 1.16 and as a compound before.
+fn read_position(compound: &NbtCompound) -> Result<[i32; 3], ChunkLoadError> {
+    match compound.get("pos") {
+        Some(NbtTag::IntArray(position)) if position.len() == 3 => Ok([position[0], position[1], position[2]]),
+        Some(NbtTag::Compound(position)) => match (position.get_int("X"), position.get_int("Y"), position.get_int("Z")) {
+            (Some(x), Some(y), Some(z)) => Ok([x, y, z]),
+            _ => Err(ChunkLoadError::MalformedChunk("POI record position is incomplete".to_owned()))
+        },
+        _ => Err(ChunkLoadError::MalformedChunk("POI record has no position".to_owned()))
+    }
+}
@@ -0,0 +1,337 @@
+use std::borrow::Cow;
+use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
+use std::ops::Deref;
+
+use crab_nbt::Nbt;
+
+use crate::{check_chunk_coordinates, get_chunk_index, read_chunk_info, read_payload, ENTRY_SIZE, HEADER_SECTORS, LOCATION_SIZE_FACTOR, TABLE_SIZE};
+use crate::chunks::{encode_nbt, Chunk, CompressionFormat};
+use crate::container::{ChunkData, RawChunk, Slot};
+use crate::error::ChunkLoadError;
+use crate::external::{ExternalChunkResolver, EXTERNAL_FLAG};
+use crate::metadata::{ChunkInfo, ChunkTimestamp, LocationTableEntry, RegionHeader};
+use crate::writer::prepare_payload;
+
+/// Storage that can be shrunk, required for compacting region files.
+pub trait SetLen {
+    fn set_len(&mut self, len: u64) -> std::io::Result<()>;
+}
+impl SetLen for std::fs::File {
+    fn set_len(&mut self, len: u64) -> std::io::Result<()> {
+        std::fs::File::set_len(self, len)
+    }
+}
+impl SetLen for std::io::Cursor<Vec<u8>> {
+    fn set_len(&mut self, len: u64) -> std::io::Result<()> {
+        self.get_mut().resize(len as usize, 0);
+        Ok(())
+    }
+}
+impl<T: SetLen> SetLen for &mut T {
+    fn set_len(&mut self, len: u64) -> std::io::Result<()> {
+        (**self).set_len(len)
+    }
+}
+
+/// A region file opened for reading and writing.
+/// Unlike [`crate::RegionFileWriter`] all changes are applied to the file immediately,
+/// only touching the sectors of the modified chunk and its header entries.
+pub struct RegionFile<F: Read + Write + Seek> {
+    file: F,
+    compression: CompressionFormat,
+    header: RegionHeader,
+    external: Option<Box<dyn ExternalChunkResolver>>
+}
+impl<F: Read + Write + Seek> RegionFile<F> {
+    /// Opens an existing region file.
+    /// Chunks written through this handle are compressed using the given format.
+    pub fn open(mut file: F, compression: CompressionFormat) -> std::io::Result<Self> {
+        file.seek(SeekFrom::Start(0))?;
+        Ok(RegionFile {
+            header: RegionHeader::read(&mut file)?,
+            file,
+            compression,
+            external: None
+        })
+    }
+
+    /// Initialises an empty region file, overwriting any header that might be present.
+    pub fn create(mut file: F, compression: CompressionFormat) -> std::io::Result<Self> {
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(&[0u8; 2 * TABLE_SIZE * ENTRY_SIZE])?;
+        Ok(RegionFile {
+            header: RegionHeader::empty(),
+            file,
+            compression,
+            external: None
+        })
+    }
+
+    /// Sets the resolver used to load and store chunks that do not fit into the region file.
+    pub fn with_external_resolver(mut self, resolver: impl ExternalChunkResolver + 'static) -> Self {
+        self.external = Some(Box::new(resolver));
+        self
+    }
+
+    pub fn get_compression(&self) -> CompressionFormat {
+        self.compression
+    }
+
+    pub fn set_compression(&mut self, compression: CompressionFormat) {
+        self.compression = compression;
+    }
+
+    /// Reads the location and payload header of the chunk, without loading the chunk itself.
+    /// See [`crate::RegionFileReader::get_chunk_info`].
+    pub fn get_chunk_info(&mut self, chunk_x: u8, chunk_z: u8) -> std::io::Result<Option<ChunkInfo>> {
+        read_chunk_info(&mut self.file, self.header.get_location(chunk_x, chunk_z)?)
+    }
+
+    /// Reads the sectors of the slot without decoding them.
+    pub fn read_slot(&mut self, chunk_x: u8, chunk_z: u8) -> Result<Slot<'_>, ChunkLoadError> {
+        let payload = read_payload(&mut self.file, self.header.get_location(chunk_x, chunk_z)?)?;
+        Ok(Slot::new(Cow::Owned(payload), self.external.as_deref(), chunk_x, chunk_z))
+    }
+
+    pub fn get_chunk(&mut self, chunk_x: u8, chunk_z: u8) -> Result<Chunk, ChunkLoadError> {
+        self.get_chunk_as(chunk_x, chunk_z)
+    }
+
+    /// See [`Slot::decode`].
+    pub fn get_chunk_as<T: ChunkData>(&mut self, chunk_x: u8, chunk_z: u8) -> Result<T, ChunkLoadError> {
+        self.read_slot(chunk_x, chunk_z)?.decode()
+    }
+
+    /// See [`Slot::decompress`].
+    pub fn get_chunk_bytes(&mut self, chunk_x: u8, chunk_z: u8) -> Result<Vec<u8>, ChunkLoadError> {
+        self.read_slot(chunk_x, chunk_z)?.decompress().map(Cow::into_owned)
+    }
+
+    /// See [`Slot::into_raw`].
+    pub fn get_raw_chunk(&mut self, chunk_x: u8, chunk_z: u8) -> Result<RawChunk, ChunkLoadError> {
+        self.read_slot(chunk_x, chunk_z)?.into_raw()
+    }
+
+    /// Replaces the chunk at the specified position or adds it, if it does not exist yet.
+    /// The chunk's current sectors are reused if the new data fits into them.
+    pub fn write_chunk(&mut self, chunk_x: u8, chunk_z: u8, chunk: &Chunk, timestamp: ChunkTimestamp) -> std::io::Result<()> {
+        self.write_nbt(chunk_x, chunk_z, &chunk.data, timestamp)
+    }
+
+    /// Writes arbitrary NBT as the chunk at the specified position.
+    /// No validation is performed on the NBT structure.
+    pub fn write_nbt(&mut self, chunk_x: u8, chunk_z: u8, nbt: &Nbt, timestamp: ChunkTimestamp) -> std::io::Result<()> {
+        self.write_payload(chunk_x, chunk_z, encode_nbt(nbt, self.compression)?, timestamp)
+    }
+
+    /// Writes the compressed data unchanged, e.g. as returned by [`crate::RegionFileReader::get_raw_chunk`].
+    /// The compression format of this handle is ignored.
+    pub fn write_raw_chunk(&mut self, chunk_x: u8, chunk_z: u8, chunk: &RawChunk, timestamp: ChunkTimestamp) -> std::io::Result<()> {
+        self.write_payload(chunk_x, chunk_z, chunk.to_payload()?, timestamp)
+    }
+
+    fn write_payload(&mut self, chunk_x: u8, chunk_z: u8, payload: Vec<u8>, timestamp: ChunkTimestamp) -> std::io::Result<()> {
+        let (payload, sectors) = prepare_payload(payload, self.external.as_deref_mut(), chunk_x, chunk_z)?;
+        // Only the region file is read, so this is unaffected by the external chunk being written already
+        let index = get_chunk_index(chunk_x, chunk_z);
+        let was_external = self.is_external(index)?;
+        if was_external && payload[4] & EXTERNAL_FLAG == 0 {
+            self.remove_external(chunk_x, chunk_z)?;
+        }
+
+        let (used, file_sectors) = self.get_used_sectors(index)?;
+        let current = self.header.location_table[index].sectors();
+        // Damaged entries may point into the header or at another chunk's sectors, which must not be overwritten
+        let reusable = !current.is_empty() && sectors as u32 <= current.len() as u32
+            && current.start >= HEADER_SECTORS && current.end <= file_sectors
+            && !current.clone().any(|sector| used[sector as usize]);
+        let position = if reusable {
+            current.start
+        } else {
+            allocate(&used, sectors)
+        };
+
+        self.file.seek(SeekFrom::Start(position as u64 * LOCATION_SIZE_FACTOR as u64))?;
+        self.file.write_all(&payload)?;
+        // Pad to the sector boundary so the file length stays a multiple of 4096
+        let padding = sectors as usize * LOCATION_SIZE_FACTOR - payload.len();
+        self.file.write_all(&vec![0u8; padding])?;
+
+        self.header.location_table[index] = LocationTableEntry::new(position, sectors);
+        self.header.timestamp_table[index] = timestamp;
+        self.write_header_entry(index)
+    }
+
+    /// Removes the chunk from the region file.
+    /// The sectors previously occupied by the chunk are left in place, but may be reused by later writes.
+    pub fn delete_chunk(&mut self, chunk_x: u8, chunk_z: u8) -> std::io::Result<()> {
+        check_chunk_coordinates(chunk_x, chunk_z)?;
+        let index = get_chunk_index(chunk_x, chunk_z);
+        if self.is_external(index)? {
+            self.remove_external(chunk_x, chunk_z)?;
+        }
+        self.header.location_table[index] = LocationTableEntry::new(0, 0);
+        self.header.timestamp_table[index] = 0;
+        self.write_header_entry(index)
+    }
+
+    pub fn set_timestamp(&mut self, chunk_x: u8, chunk_z: u8, timestamp: ChunkTimestamp) -> std::io::Result<()> {
+        check_chunk_coordinates(chunk_x, chunk_z)?;
+        let index = get_chunk_index(chunk_x, chunk_z);
+        self.header.timestamp_table[index] = timestamp;
+        self.write_header_entry(index)
+    }
+
+    /// Moves all chunks to the front of the file, removing unused sectors between them,
+    /// and truncates the file afterwards.
+    /// Returns the number of bytes that were reclaimed.
+    /// The file may temporarily grow by the size of the largest chunk.
+    ///
+    /// Fails with [`std::io::ErrorKind::InvalidData`] without modifying the file,
+    /// if chunks overlap each other or the header, or extend past the end of the file.
+    /// See [`crate::RegionFileReader::repair`] for such files.
+    pub fn compact(&mut self) -> std::io::Result<u64> where F: SetLen {
+        let old_length = self.file.seek(SeekFrom::End(0))?;
+
+        let mut live: Vec<usize> = (0..TABLE_SIZE)
+            .filter(|i| !self.header.location_table[*i].is_empty())
+            .collect();
+        live.sort_by_key(|i| self.header.location_table[*i].sectors().start);
+
+        // Moving chunks is only safe if no sectors are shared, otherwise a chunk could be overwritten before it is read
+        let mut previous_end = HEADER_SECTORS;
+        for index in &live {
+            let sectors = self.header.location_table[*index].sectors();
+            if sectors.start < previous_end {
+                return Err(std::io::Error::new(ErrorKind::InvalidData, "Chunks overlap each other or the header"));
+            }
+            if sectors.end as u64 * LOCATION_SIZE_FACTOR as u64 > old_length {
+                return Err(std::io::Error::new(ErrorKind::InvalidData, "Chunk extends past the end of the file"));
+            }
+            previous_end = sectors.end;
+        }
+
+        // Chunks only ever move towards the start of the file.
+        //  Since they are processed in order of their position, a chunk is always read before it could be overwritten.
+        //  The header entry is updated after every move, so an interrupted compaction leaves a readable file behind.
+        //  A chunk moving onto its own sectors is copied past the end of the file first,
+        //  so that its entry never points at partially overwritten sectors.
+        let scratch_sector = old_length.div_ceil(LOCATION_SIZE_FACTOR as u64) as u32;
+        let mut next_sector = HEADER_SECTORS;
+        for index in live {
+            let (offset, size) = self.header.location_table[index].to_offset_form();
+            let current = self.header.location_table[index].sectors();
+            let sectors = current.len() as u8;
+            if current.start != next_sector {
+                let mut buf = vec![0u8; size];
+                self.file.seek(SeekFrom::Start(offset))?;
+                self.file.read_exact(&mut buf)?;
+                if next_sector + sectors as u32 > current.start {
+                    self.move_chunk(index, &buf, scratch_sector)?;
+                }
+                self.move_chunk(index, &buf, next_sector)?;
+            }
+            next_sector += sectors as u32;
+        }
+
+        let new_length = next_sector as u64 * LOCATION_SIZE_FACTOR as u64;
+        self.file.set_len(new_length)?;
+        Ok(old_length.saturating_sub(new_length))
+    }
+
+    /// Writes the chunk's sectors to the new position and updates its header entry.
+    fn move_chunk(&mut self, index: usize, buf: &[u8], position: u32) -> std::io::Result<()> {
+        self.file.seek(SeekFrom::Start(position as u64 * LOCATION_SIZE_FACTOR as u64))?;
+        self.file.write_all(buf)?;
+        self.header.location_table[index] = LocationTableEntry::new(position, self.header.location_table[index].get_sector_count());
+        self.write_header_entry(index)
+    }
+
+    /// Flushes and returns the underlying file.
+    pub fn into_inner(mut self) -> std::io::Result<F> {
+        self.file.flush()?;
+        Ok(self.file)
+    }
+
+    /// Marks the sectors used by the header and by all chunks except the one at `index`.
+    /// Also returns the length of the file in sectors.
+    fn get_used_sectors(&mut self, index: usize) -> std::io::Result<(Vec<bool>, u32)> {
+        let file_sectors = self.file.seek(SeekFrom::End(0))?.div_ceil(LOCATION_SIZE_FACTOR as u64) as u32;
+        let mut used = vec![false; file_sectors.max(HEADER_SECTORS) as usize];
+        used[..HEADER_SECTORS as usize].fill(true);
+        for (i, entry) in self.header.location_table.iter().enumerate() {
+            if i == index {
+                continue;
+            }
+            // Entries pointing past the end of the file still claim their sectors,
+            //  otherwise appending to the file would overwrite whatever they expect there
+            if entry.sectors().end as usize > used.len() {
+                used.resize(entry.sectors().end as usize, false);
+            }
+            for sector in entry.sectors() {
+                used[sector as usize] = true;
+            }
+        }
+        Ok((used, file_sectors))
+    }
+
+    /// Checks whether the chunk's payload is stored outside of the region file
+    /// Entries that don't point at sectors within the file are treated as not external,
+    /// so that damaged slots can still be deleted or overwritten.
+    fn is_external(&mut self, index: usize) -> std::io::Result<bool> {
+        let sectors = self.header.location_table[index].sectors();
+        let file_length = self.file.seek(SeekFrom::End(0))?;
+        if sectors.is_empty() || sectors.start < HEADER_SECTORS || sectors.end as u64 * LOCATION_SIZE_FACTOR as u64 > file_length {
+            return Ok(false);
+        }
+        let (offset, _) = self.header.location_table[index].to_offset_form();
+        let mut header = [0u8; 5];
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.read_exact(&mut header)?;
+        Ok(header[4] & EXTERNAL_FLAG != 0)
+    }
+
+    fn remove_external(&mut self, chunk_x: u8, chunk_z: u8) -> std::io::Result<()> {
+        match self.external.as_deref_mut() {
+            Some(resolver) => resolver.remove_external(chunk_x, chunk_z),
+            None => Ok(())
+        }
+    }
+
+    fn write_header_entry(&mut self, index: usize) -> std::io::Result<()> {
+        self.file.seek(SeekFrom::Start((index * ENTRY_SIZE) as u64))?;
+        self.file.write_all(&self.header.location_table[index].to_bytes())?;
+        self.file.seek(SeekFrom::Start((TABLE_SIZE * ENTRY_SIZE + index * ENTRY_SIZE) as u64))?;
+        self.file.write_all(&self.header.timestamp_table[index].to_be_bytes())
+    }
+}
+
+impl<F: Read + Write + Seek> Deref for RegionFile<F> {
+    type Target = RegionHeader;
+
+    fn deref(&self) -> &Self::Target {
+        &self.header
+    }
+}
+
+/// Finds the first run of free sectors that can hold the requested amount.
+/// If no such run exists the sectors are allocated at the end of the file.
+fn allocate(used: &[bool], sectors: u8) -> u32 {
+    let mut run_start = 0;
+    let mut run_length = 0;
+    for (sector, is_used) in used.iter().enumerate() {
+        if *is_used {
+            run_length = 0;
+            continue;
+        }
+        if run_length == 0 {
+            run_start = sector;
+        }
+        run_length += 1;
+        if run_length == sectors as usize {
+            return run_start as u32;
+        }
+    }
+    // Extend a free run at the end of the file instead of leaving it unused
+    if run_length > 0 { run_start as u32 } else { used.len() as u32 }
+}
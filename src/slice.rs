@@ -0,0 +1,135 @@
+use std::borrow::Cow;
+use std::io::{Cursor, ErrorKind};
+use std::ops::Deref;
+
+use crate::{read_chunk_info, CHUNKS_PER_AXIS};
+use crate::chunks::Chunk;
+use crate::container::{ChunkData, RawChunk, Slot};
+use crate::error::ChunkLoadError;
+use crate::external::ExternalChunkResolver;
+use crate::metadata::{ChunkInfo, RegionHeader};
+
+/// A region file held entirely in memory, e.g. as a memory-mapped file.
+/// Since chunks are decoded straight from the underlying buffer, all methods take `&self`
+/// and the reader can be shared between threads.
+pub struct SliceRegionReader<B: AsRef<[u8]>> {
+    data: B,
+    header: RegionHeader,
+    external: Option<Box<dyn ExternalChunkResolver>>
+}
+impl<B: AsRef<[u8]>> SliceRegionReader<B> {
+    pub fn create(data: B) -> std::io::Result<Self> {
+        Ok(SliceRegionReader {
+            header: RegionHeader::read(&mut data.as_ref())?,
+            data,
+            external: None
+        })
+    }
+
+    /// Sets the resolver used to load chunks that are stored outside of the region file.
+    /// Without a resolver these chunks fail to load with [`ChunkLoadError::ExternalChunkUnavailable`].
+    pub fn with_external_resolver(mut self, resolver: impl ExternalChunkResolver + 'static) -> Self {
+        self.external = Some(Box::new(resolver));
+        self
+    }
+
+    /// Reads the location and payload header of the chunk, without loading the chunk itself.
+    /// See [`crate::RegionFileReader::get_chunk_info`].
+    pub fn get_chunk_info(&self, chunk_x: u8, chunk_z: u8) -> std::io::Result<Option<ChunkInfo>> {
+        read_chunk_info(&mut Cursor::new(self.data.as_ref()), self.header.get_location(chunk_x, chunk_z)?)
+    }
+
+    /// Returns the sectors occupied by the chunk without copying them.
+    pub fn get_payload(&self, chunk_x: u8, chunk_z: u8) -> Result<&[u8], ChunkLoadError> {
+        let (seek, size) = self.header.get_location(chunk_x, chunk_z)?.to_offset_form();
+        if size == 0 {
+            return Err(ChunkLoadError::ChunkDoesNotExist)
+        }
+        self.data.as_ref()
+            .get(seek as usize..seek as usize + size)
+            .ok_or_else(|| std::io::Error::new(ErrorKind::UnexpectedEof, "Chunk lies outside of the region file").into())
+    }
+
+    /// Reads the sectors of the slot without decoding or copying them.
+    pub fn read_slot(&self, chunk_x: u8, chunk_z: u8) -> Result<Slot<'_>, ChunkLoadError> {
+        Ok(Slot::new(Cow::Borrowed(self.get_payload(chunk_x, chunk_z)?), self.external.as_deref(), chunk_x, chunk_z))
+    }
+
+    pub fn get_chunk(&self, chunk_x: u8, chunk_z: u8) -> Result<Chunk, ChunkLoadError> {
+        self.get_chunk_as(chunk_x, chunk_z)
+    }
+
+    /// See [`Slot::decode`].
+    pub fn get_chunk_as<T: ChunkData>(&self, chunk_x: u8, chunk_z: u8) -> Result<T, ChunkLoadError> {
+        self.read_slot(chunk_x, chunk_z)?.decode()
+    }
+
+    /// See [`Slot::decompress`].
+    pub fn get_chunk_bytes(&self, chunk_x: u8, chunk_z: u8) -> Result<Cow<'_, [u8]>, ChunkLoadError> {
+        self.read_slot(chunk_x, chunk_z)?.decompress()
+    }
+
+    /// See [`Slot::into_raw`].
+    pub fn get_raw_chunk(&self, chunk_x: u8, chunk_z: u8) -> Result<RawChunk, ChunkLoadError> {
+        self.read_slot(chunk_x, chunk_z)?.into_raw()
+    }
+
+    pub fn get_chunks(&self) -> impl Iterator<Item = ([u8; 2], Option<Result<Chunk, ChunkLoadError>>)> + '_ {
+        self.get_chunks_as()
+    }
+
+    /// Iterates over all slots, loading the existing ones as any type stored in Anvil containers.
+    pub fn get_chunks_as<T: ChunkData>(&self) -> impl Iterator<Item = ([u8; 2], Option<Result<T, ChunkLoadError>>)> + '_ {
+        (0..CHUNKS_PER_AXIS)
+            .flat_map(|z| (0..CHUNKS_PER_AXIS).map(move |x| [x, z]))
+            .map(|[x, z]| ([x, z], if self.get_timestamp(x, z).unwrap() == 0 {
+                None
+            } else {
+                Some(self.get_chunk_as(x, z))
+            }))
+    }
+
+    pub fn into_inner(self) -> B {
+        self.data
+    }
+}
+impl<B: AsRef<[u8]>> Deref for SliceRegionReader<B> {
+    type Target = RegionHeader;
+
+    fn deref(&self) -> &Self::Target {
+        &self.header
+    }
+}
+
+#[cfg(feature = "memmap")]
+impl SliceRegionReader<memmap2::Mmap> {
+    /// Memory-maps the region file at the path.
+    /// If the file name follows the vanilla pattern, external chunks are resolved from the same directory.
+    ///
+    /// # Safety
+    /// The file must not be modified while it is mapped, see [`memmap2::Mmap`] for details.
+    pub unsafe fn open_mmap(path: &std::path::Path) -> std::io::Result<Self> {
+        let file = std::fs::File::open(path)?;
+        // SAFETY: Upheld by the caller
+        let reader = SliceRegionReader::create(unsafe { memmap2::Mmap::map(&file)? })?;
+        Ok(match crate::external::DirectoryResolver::for_region_file(path) {
+            Some(resolver) => reader.with_external_resolver(resolver),
+            None => reader
+        })
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<B: AsRef<[u8]> + Sync> SliceRegionReader<B> {
+    /// Decodes all existing chunks on the rayon thread pool.
+    /// The order of the results is unspecified.
+    pub fn par_get_chunks(&self) -> impl rayon::iter::ParallelIterator<Item = ([u8; 2], Result<Chunk, ChunkLoadError>)> + '_ {
+        use rayon::iter::{IntoParallelIterator, ParallelIterator};
+        (0..CHUNKS_PER_AXIS as usize * CHUNKS_PER_AXIS as usize).into_par_iter()
+            .filter(|i| !self.header.location_table[*i].is_empty())
+            .map(|i| {
+                let [x, z] = [(i % CHUNKS_PER_AXIS as usize) as u8, (i / CHUNKS_PER_AXIS as usize) as u8];
+                ([x, z], self.get_chunk(x, z))
+            })
+    }
+}
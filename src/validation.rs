@@ -0,0 +1,376 @@
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crab_nbt::Nbt;
+
+use crate::{get_chunk_index, read_payload, RegionFileReader, RegionFileWriter, CHUNKS_PER_AXIS, HEADER_SECTORS, LOCATION_SIZE_FACTOR};
+use crate::chunks::{Chunk, CompressionFormat};
+use crate::error::ChunkLoadError;
+use crate::external::{ExternalChunkResolver, EXTERNAL_FLAG};
+use crate::metadata::{ChunkTimestamp, LocationTableEntry};
+
+// Deeper structures are not produced by the game and would risk overflowing the stack
+const MAX_NBT_DEPTH: usize = 512;
+
+/// A problem found while validating a single chunk.
+#[derive(Debug)]
+pub enum ChunkIssue {
+    /// The location entry has a position, but no sectors
+    NoSectors,
+    /// The chunk's sectors include the location or timestamp table
+    OverlapsHeader,
+    /// The chunk's sectors extend beyond the end of the file
+    PastEndOfFile,
+    /// The chunk shares sectors with the chunk at the given coordinates
+    Overlaps([u8; 2]),
+    /// The length prefix does not fit into the allocated sectors
+    LengthExceedsSectors { length: u32, allocated: usize },
+    /// The length prefix is 0, i.e. there is not even a compression byte
+    ZeroLength,
+    UnknownCompression(u8),
+    /// The chunk is stored externally, but the reader has no resolver to locate it
+    ExternalChunkUnavailable,
+    /// Reading the chunk's sectors or external data failed
+    IOError(std::io::Error),
+    /// The payload could not be decompressed using the specified compression format
+    Decompression(std::io::Error),
+    /// The decompressed data is not valid NBT
+    MalformedNbt(String),
+    /// The NBT is valid, but does not describe a valid chunk
+    InvalidChunk(ChunkLoadError),
+    /// The chunk's own position does not belong to its slot in the region
+    Misplaced([i32; 2]),
+}
+
+#[derive(Debug)]
+pub struct ChunkReport {
+    pub coordinates: [u8; 2],
+    pub location: LocationTableEntry,
+    pub issues: Vec<ChunkIssue>
+}
+impl ChunkReport {
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// The result of validating a region file.
+/// Contains a report for every chunk with a location entry, in the order of the location table.
+#[derive(Debug)]
+pub struct ValidationReport {
+    pub file_length: u64,
+    pub chunks: Vec<ChunkReport>
+}
+impl ValidationReport {
+    pub fn is_ok(&self) -> bool {
+        self.chunks.iter().all(ChunkReport::is_ok)
+    }
+
+    /// Iterates over the reports of all chunks that have at least one issue.
+    pub fn problems(&self) -> impl Iterator<Item = &ChunkReport> {
+        self.chunks.iter().filter(|report| !report.is_ok())
+    }
+}
+
+/// The result of repairing a region file.
+#[derive(Debug, Default)]
+pub struct RepairReport {
+    /// Chunks that were copied using their location entry
+    pub salvaged: Vec<[u8; 2]>,
+    /// Salvaged chunks whose location entry had the wrong sector count
+    pub fixed: Vec<[u8; 2]>,
+    /// Chunks without a usable location entry that were found by scanning the sectors
+    pub recovered: Vec<[u8; 2]>,
+    /// Chunks that could not be salvaged and the reason why
+    pub dropped: Vec<([u8; 2], ChunkIssue)>
+}
+
+impl<R: Read + Seek> RegionFileReader<R> {
+    /// Checks every chunk in the region file, collecting all problems instead of stopping at the first one.
+    /// Only failing to determine the file length is reported as an error.
+    pub fn validate(&mut self) -> std::io::Result<ValidationReport> {
+        let file_length = self.reader.seek(SeekFrom::End(0))?;
+        let file_sectors = file_length.div_ceil(LOCATION_SIZE_FACTOR as u64);
+
+        let mut chunks = Vec::new();
+        for z in 0..CHUNKS_PER_AXIS {
+            for x in 0..CHUNKS_PER_AXIS {
+                let location = self.header.location_table[get_chunk_index(x, z)].clone();
+                if location.is_empty() {
+                    continue;
+                }
+                let mut issues = Vec::new();
+                let sectors = location.sectors();
+                if sectors.is_empty() {
+                    issues.push(ChunkIssue::NoSectors);
+                } else if sectors.start < HEADER_SECTORS {
+                    issues.push(ChunkIssue::OverlapsHeader);
+                }
+                if sectors.end as u64 > file_sectors {
+                    issues.push(ChunkIssue::PastEndOfFile);
+                }
+                let located = issues.is_empty();
+                for (i, other) in self.header.location_table.iter().enumerate() {
+                    if i != get_chunk_index(x, z) && !other.is_empty()
+                        && other.sectors().start < sectors.end && sectors.start < other.sectors().end {
+                        issues.push(ChunkIssue::Overlaps([
+                            (i % CHUNKS_PER_AXIS as usize) as u8, (i / CHUNKS_PER_AXIS as usize) as u8
+                        ]));
+                    }
+                }
+                // Reading is pointless if the chunk isn't located properly
+                if located {
+                    issues.extend(self.validate_payload(x, z).err());
+                }
+                chunks.push(ChunkReport { coordinates: [x, z], location, issues });
+            }
+        }
+        Ok(ValidationReport { file_length, chunks })
+    }
+
+    fn validate_payload(&mut self, chunk_x: u8, chunk_z: u8) -> Result<(), ChunkIssue> {
+        let buf = match read_payload(&mut self.reader, &self.header.location_table[get_chunk_index(chunk_x, chunk_z)]) {
+            Ok(buf) => buf,
+            Err(ChunkLoadError::IOError(e)) => return Err(ChunkIssue::IOError(e)),
+            Err(e) => return Err(ChunkIssue::InvalidChunk(e))
+        };
+        let length = u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]);
+        if length == 0 {
+            return Err(ChunkIssue::ZeroLength);
+        }
+        if length as usize + 4 > buf.len() {
+            return Err(ChunkIssue::LengthExceedsSectors { length, allocated: buf.len() });
+        }
+        decode_checked(&buf[..length as usize + 4], self.external.as_deref(), Some([chunk_x, chunk_z]))?;
+        Ok(())
+    }
+
+    /// Copies every chunk that can be decoded into the writer, dropping all others.
+    /// Chunks are read using the length prefix in their first sector,
+    /// so location entries with a wrong sector count are fixed along the way.
+    ///
+    /// If `scan_sectors` is set, the sectors not used by any salvaged chunk are searched for chunk headers,
+    /// recovering chunks whose location entry was lost. Their position is taken from the chunk data.
+    pub fn repair<W: Write + Seek>(&mut self, writer: &mut RegionFileWriter<W>, scan_sectors: bool) -> std::io::Result<RepairReport> {
+        let file_length = self.reader.seek(SeekFrom::End(0))?;
+        let file_sectors = file_length.div_ceil(LOCATION_SIZE_FACTOR as u64) as u32;
+        let mut used = vec![false; file_sectors.max(HEADER_SECTORS) as usize];
+        used[..HEADER_SECTORS as usize].fill(true);
+        let mut report = RepairReport::default();
+
+        for z in 0..CHUNKS_PER_AXIS {
+            for x in 0..CHUNKS_PER_AXIS {
+                let index = get_chunk_index(x, z);
+                let location = self.header.location_table[index].clone();
+                if location.is_empty() {
+                    continue;
+                }
+                let start = location.get_sector_offset();
+                let result = if start < HEADER_SECTORS {
+                    Err(ChunkIssue::OverlapsHeader)
+                } else if start >= file_sectors {
+                    Err(ChunkIssue::PastEndOfFile)
+                } else {
+                    self.salvage(start, file_length, Some([x, z]))
+                };
+                match result {
+                    Ok((chunk, sectors)) => {
+                        writer.write_nbt(x, z, &chunk.data, self.header.timestamp_table[index])?;
+                        used[start as usize..(start + sectors) as usize].fill(true);
+                        if sectors != location.get_sector_count() as u32 {
+                            report.fixed.push([x, z]);
+                        }
+                        report.salvaged.push([x, z]);
+                    },
+                    Err(issue) => report.dropped.push(([x, z], issue))
+                }
+            }
+        }
+
+        if scan_sectors {
+            let mut sector = HEADER_SECTORS;
+            while sector < file_sectors {
+                let recovered = if used[sector as usize] {
+                    None
+                } else {
+                    self.recover(sector, file_length, writer, &mut report)?
+                };
+                sector += recovered.unwrap_or(1);
+            }
+        }
+        Ok(report)
+    }
+
+    /// Tries to recover a chunk starting at the sector, writing it if its slot is still free.
+    /// Returns the number of sectors used by the chunk, if it was recovered.
+    fn recover<W: Write + Seek>(
+        &mut self, sector: u32, file_length: u64, writer: &mut RegionFileWriter<W>, report: &mut RepairReport
+    ) -> std::io::Result<Option<u32>> {
+        let Ok((chunk, sectors)) = self.salvage(sector, file_length, None) else {
+            return Ok(None);
+        };
+        let Ok([x, z]) = chunk.get_position() else {
+            return Ok(None);
+        };
+        let coordinates = [x.rem_euclid(CHUNKS_PER_AXIS as i32) as u8, z.rem_euclid(CHUNKS_PER_AXIS as i32) as u8];
+        if report.salvaged.contains(&coordinates) || report.recovered.contains(&coordinates) {
+            return Ok(None);
+        }
+
+        let mut timestamp = self.header.timestamp_table[get_chunk_index(coordinates[0], coordinates[1])];
+        if timestamp == 0 {
+            // Chunks without a timestamp are treated as missing
+            timestamp = SystemTime::now().duration_since(UNIX_EPOCH)
+                .map_or(1, |time| time.as_secs() as ChunkTimestamp);
+        }
+        writer.write_nbt(coordinates[0], coordinates[1], &chunk.data, timestamp)?;
+        report.recovered.push(coordinates);
+        report.dropped.retain(|(dropped, _)| *dropped != coordinates);
+        Ok(Some(sectors))
+    }
+
+    /// Decodes the chunk starting at the sector, using the length prefix instead of the location entry.
+    /// Returns the chunk and the number of sectors it occupies.
+    fn salvage(&mut self, sector: u32, file_length: u64, coordinates: Option<[u8; 2]>) -> Result<(Chunk, u32), ChunkIssue> {
+        let offset = sector as u64 * LOCATION_SIZE_FACTOR as u64;
+        let mut header = [0u8; 5];
+        self.reader.seek(SeekFrom::Start(offset))
+            .and_then(|_| self.reader.read_exact(&mut header))
+            .map_err(ChunkIssue::IOError)?;
+        let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+        if length == 0 {
+            return Err(ChunkIssue::ZeroLength);
+        }
+        // Checked before reading the rest, since scanning tries every sector
+        CompressionFormat::try_from(header[4] & !EXTERNAL_FLAG)
+            .map_err(|_| ChunkIssue::UnknownCompression(header[4]))?;
+        if offset + 4 + length as u64 > file_length {
+            return Err(ChunkIssue::PastEndOfFile);
+        }
+
+        let mut buf = vec![0u8; length as usize + 4];
+        self.reader.seek(SeekFrom::Start(offset))
+            .and_then(|_| self.reader.read_exact(&mut buf))
+            .map_err(ChunkIssue::IOError)?;
+        let chunk = decode_checked(&buf, self.external.as_deref(), coordinates)?;
+        Ok((chunk, buf.len().div_ceil(LOCATION_SIZE_FACTOR) as u32))
+    }
+}
+
+/// Decodes a complete payload, including the length prefix, without panicking on malformed data.
+/// External chunks can only be resolved if the coordinates are known.
+fn decode_checked(
+    buf: &[u8], external: Option<&dyn ExternalChunkResolver>, coordinates: Option<[u8; 2]>
+) -> Result<Chunk, ChunkIssue> {
+    let compression = buf[4];
+    let external_data;
+    let compressed = if compression & EXTERNAL_FLAG == 0 {
+        &buf[5..]
+    } else {
+        let [chunk_x, chunk_z] = coordinates.ok_or(ChunkIssue::ExternalChunkUnavailable)?;
+        external_data = external
+            .ok_or(ChunkIssue::ExternalChunkUnavailable)?
+            .read_external(chunk_x, chunk_z)
+            .map_err(ChunkIssue::IOError)?;
+        &external_data[..]
+    };
+    let format = CompressionFormat::try_from(compression & !EXTERNAL_FLAG)
+        .map_err(|_| ChunkIssue::UnknownCompression(compression))?;
+    let decompressed = format.decompress(compressed).map_err(ChunkIssue::Decompression)?;
+
+    check_nbt(&decompressed).map_err(ChunkIssue::MalformedNbt)?;
+    let nbt = Nbt::read(&mut &decompressed[..])
+        .map_err(|e| ChunkIssue::MalformedNbt(e.to_string()))?;
+    let chunk = Chunk::from_nbt(nbt).map_err(ChunkIssue::InvalidChunk)?;
+
+    if let (Some(expected), Ok(position)) = (coordinates, chunk.get_position()) {
+        let local = position.map(|component| component.rem_euclid(CHUNKS_PER_AXIS as i32) as u8);
+        if local != expected {
+            return Err(ChunkIssue::Misplaced(position));
+        }
+    }
+    Ok(chunk)
+}
+
+/// Walks the structure of the NBT data, checking all lengths and tag ids.
+/// The NBT parser panics on truncated data, so anything of unknown origin has to pass this first.
+pub(crate) fn check_nbt(bytes: &[u8]) -> Result<(), String> {
+    let mut cursor = NbtCursor { bytes, position: 0 };
+    if cursor.take(1)?[0] != 10 {
+        return Err("Root tag is not a compound".to_owned());
+    }
+    cursor.skip_string()?;
+    cursor.skip_payload(10, 0)
+}
+
+struct NbtCursor<'a> {
+    bytes: &'a [u8],
+    position: usize
+}
+impl<'a> NbtCursor<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], String> {
+        let result = self.bytes.get(self.position..self.position.saturating_add(length))
+            .ok_or_else(|| format!("Data ends unexpectedly at byte {}", self.position))?;
+        self.position += length;
+        Ok(result)
+    }
+
+    fn read_length(&mut self) -> Result<usize, String> {
+        let length = i32::from_be_bytes(self.take(4)?.try_into().unwrap());
+        usize::try_from(length).map_err(|_| format!("Negative length at byte {}", self.position - 4))
+    }
+
+    fn skip_string(&mut self) -> Result<(), String> {
+        let length = u16::from_be_bytes(self.take(2)?.try_into().unwrap());
+        let start = self.position;
+        cesu8::from_java_cesu8(self.take(length as usize)?)
+            .map_err(|_| format!("Invalid string at byte {start}"))?;
+        Ok(())
+    }
+
+    fn skip_payload(&mut self, tag_id: u8, depth: usize) -> Result<(), String> {
+        if depth > MAX_NBT_DEPTH {
+            return Err("NBT is nested too deeply".to_owned());
+        }
+        match tag_id {
+            0 => {},
+            1 => { self.take(1)?; },
+            2 => { self.take(2)?; },
+            3 | 5 => { self.take(4)?; },
+            4 | 6 => { self.take(8)?; },
+            7 => {
+                let length = self.read_length()?;
+                self.take(length)?;
+            },
+            8 => self.skip_string()?,
+            9 => {
+                let element_id = self.take(1)?[0];
+                let length = self.read_length()?;
+                if element_id == 0 && length > 0 {
+                    return Err(format!("List of end tags at byte {}", self.position));
+                }
+                for _ in 0..length {
+                    self.skip_payload(element_id, depth + 1)?;
+                }
+            },
+            10 => loop {
+                let child_id = self.take(1)?[0];
+                if child_id == 0 {
+                    break;
+                }
+                self.skip_string()?;
+                self.skip_payload(child_id, depth + 1)?;
+            },
+            11 => {
+                let length = self.read_length()?;
+                self.take(length.saturating_mul(4))?;
+            },
+            12 => {
+                let length = self.read_length()?;
+                self.take(length.saturating_mul(8))?;
+            },
+            id => return Err(format!("Unknown tag id {id} at byte {}", self.position))
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,213 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use crate::chunks::Chunk;
+use crate::chunks::sections::BlockState;
+use crate::error::ChunkLoadError;
+use crate::external::{parse_region_file_name, DirectoryResolver};
+use crate::{RegionFileReader, CHUNKS_PER_AXIS};
+
+const REGION_DIRECTORY: &str = "region";
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DimensionId {
+    Overworld,
+    Nether,
+    End,
+    /// A dimension added by a datapack, stored in `dimensions/<namespace>/<name>`
+    Custom { namespace: String, name: String }
+}
+impl DimensionId {
+    /// Returns the directory containing the region files of this dimension, relative to the world directory.
+    pub fn get_region_directory(&self) -> PathBuf {
+        match self {
+            DimensionId::Overworld => PathBuf::from(REGION_DIRECTORY),
+            DimensionId::Nether => Path::new("DIM-1").join(REGION_DIRECTORY),
+            DimensionId::End => Path::new("DIM1").join(REGION_DIRECTORY),
+            DimensionId::Custom { namespace, name } => Path::new("dimensions")
+                .join(namespace).join(name).join(REGION_DIRECTORY)
+        }
+    }
+}
+
+/// A save directory, containing one or more dimensions.
+pub struct World {
+    path: PathBuf,
+    dimensions: HashMap<DimensionId, Dimension>
+}
+impl World {
+    /// Opens the save directory and discovers its dimensions.
+    /// No region files are opened until a chunk within them is requested.
+    pub fn open(path: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let path = path.into();
+        let mut ids = vec![DimensionId::Overworld, DimensionId::Nether, DimensionId::End];
+        let custom_root = path.join("dimensions");
+        if custom_root.is_dir() {
+            for namespace in std::fs::read_dir(&custom_root)? {
+                let namespace = namespace?;
+                if !namespace.file_type()?.is_dir() {
+                    continue;
+                }
+                for name in std::fs::read_dir(namespace.path())? {
+                    let name = name?;
+                    ids.push(DimensionId::Custom {
+                        namespace: namespace.file_name().to_string_lossy().into_owned(),
+                        name: name.file_name().to_string_lossy().into_owned()
+                    });
+                }
+            }
+        }
+
+        let dimensions = ids.into_iter()
+            .map(|id| (path.join(id.get_region_directory()), id))
+            .filter(|(directory, _)| directory.is_dir())
+            .map(|(directory, id)| (id, Dimension::open(directory)))
+            .collect();
+        Ok(World { path, dimensions })
+    }
+
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn get_dimensions(&self) -> impl Iterator<Item = &DimensionId> {
+        self.dimensions.keys()
+    }
+
+    pub fn get_dimension(&mut self, id: &DimensionId) -> Option<&mut Dimension> {
+        self.dimensions.get_mut(id)
+    }
+
+    /// Loads the chunk at the specified chunk coordinates in the overworld.
+    pub fn get_chunk(&mut self, chunk_x: i32, chunk_z: i32) -> Result<Chunk, ChunkLoadError> {
+        self.get_dimension(&DimensionId::Overworld)
+            .ok_or(ChunkLoadError::ChunkDoesNotExist)?
+            .get_chunk(chunk_x, chunk_z)
+    }
+
+    /// Returns the block at the absolute coordinates in the overworld.
+    /// See [`Dimension::get_block_at`].
+    pub fn get_block_at(&mut self, x: i32, y: i32, z: i32) -> Result<Option<BlockState<'_>>, ChunkLoadError> {
+        match self.get_dimension(&DimensionId::Overworld) {
+            None => Ok(None),
+            Some(dimension) => dimension.get_block_at(x, y, z)
+        }
+    }
+}
+
+type RegionReader = RegionFileReader<BufReader<File>>;
+
+/// A directory of region files, opened lazily.
+pub struct Dimension {
+    directory: PathBuf,
+    // None marks regions that do not exist, so they don't have to be looked up repeatedly
+    regions: HashMap<[i32; 2], Option<RegionReader>>,
+    // Chunks kept around for block access, see load_chunk
+    chunks: HashMap<[i32; 2], Chunk>
+}
+impl Dimension {
+    pub fn open(directory: impl Into<PathBuf>) -> Self {
+        Dimension { directory: directory.into(), regions: HashMap::new(), chunks: HashMap::new() }
+    }
+
+    pub fn get_directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// Lists the coordinates of all region files in this dimension.
+    /// Regions existing as both Anvil and McRegion files are only listed once.
+    pub fn list_regions(&self) -> std::io::Result<Vec<[i32; 2]>> {
+        let mut regions = Vec::new();
+        for entry in std::fs::read_dir(&self.directory)? {
+            if let Some(coordinates) = entry?.file_name().to_str().and_then(parse_region_file_name) {
+                regions.push(coordinates);
+            }
+        }
+        regions.sort();
+        regions.dedup();
+        Ok(regions)
+    }
+
+    /// Returns the region file at the specified region coordinates, opening it if necessary.
+    /// Falls back to the McRegion file (`.mcr`), if there is no Anvil file.
+    /// Returns None, if the region file does not exist or is empty.
+    pub fn get_region(&mut self, region_x: i32, region_z: i32) -> std::io::Result<Option<&mut RegionReader>> {
+        if !self.regions.contains_key(&[region_x, region_z]) {
+            // The game keeps McRegion files around after converting them, so Anvil files take precedence
+            let file = match File::open(self.directory.join(format!("r.{region_x}.{region_z}.mca"))) {
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound =>
+                    File::open(self.directory.join(format!("r.{region_x}.{region_z}.mcr"))),
+                result => result
+            };
+            let region = match file {
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+                Err(e) => return Err(e),
+                // The game sometimes leaves empty region files behind
+                Ok(file) if file.metadata()?.len() == 0 => None,
+                Ok(file) => Some(RegionFileReader::create(BufReader::new(file))?
+                    .with_external_resolver(DirectoryResolver::new(&self.directory, region_x, region_z)))
+            };
+            self.regions.insert([region_x, region_z], region);
+        }
+        Ok(self.regions.get_mut(&[region_x, region_z]).and_then(Option::as_mut))
+    }
+
+    /// Loads the chunk at the specified chunk coordinates.
+    pub fn get_chunk(&mut self, chunk_x: i32, chunk_z: i32) -> Result<Chunk, ChunkLoadError> {
+        let ([region_x, region_z], [local_x, local_z]) = split_chunk_coordinates(chunk_x, chunk_z);
+        self.get_region(region_x, region_z)?
+            .ok_or(ChunkLoadError::ChunkDoesNotExist)?
+            .get_chunk(local_x, local_z)
+    }
+
+    /// Loads the chunk at the specified chunk coordinates and keeps it in memory for subsequent calls.
+    /// Returns None, if the chunk does not exist.
+    /// Use [`Dimension::unload_chunks`] or [`Dimension::close_region`] to free the memory again.
+    pub fn load_chunk(&mut self, chunk_x: i32, chunk_z: i32) -> Result<Option<&Chunk>, ChunkLoadError> {
+        if !self.chunks.contains_key(&[chunk_x, chunk_z]) {
+            match self.get_chunk(chunk_x, chunk_z) {
+                Err(ChunkLoadError::ChunkDoesNotExist) => return Ok(None),
+                Err(e) => return Err(e),
+                Ok(chunk) => self.chunks.insert([chunk_x, chunk_z], chunk)
+            };
+        }
+        Ok(self.chunks.get(&[chunk_x, chunk_z]))
+    }
+
+    /// Returns the block at the absolute world coordinates, loading the containing chunk if necessary.
+    /// Returns None, if the chunk or section containing the position has not been generated.
+    pub fn get_block_at(&mut self, x: i32, y: i32, z: i32) -> Result<Option<BlockState<'_>>, ChunkLoadError> {
+        match self.load_chunk(x.div_euclid(16), z.div_euclid(16))? {
+            None => Ok(None),
+            Some(chunk) => chunk.get_block_at(x, y, z)
+        }
+    }
+
+    /// Removes all chunks kept in memory by [`Dimension::load_chunk`].
+    pub fn unload_chunks(&mut self) {
+        self.chunks.clear();
+    }
+
+    /// Closes all region files opened so far.
+    pub fn close_regions(&mut self) {
+        self.regions.clear();
+    }
+
+    /// Closes the region file at the specified region coordinates
+    /// and removes its chunks kept in memory by [`Dimension::load_chunk`].
+    /// The region is opened again when one of its chunks is requested.
+    pub fn close_region(&mut self, region_x: i32, region_z: i32) {
+        self.regions.remove(&[region_x, region_z]);
+        self.chunks.retain(|[chunk_x, chunk_z], _| split_chunk_coordinates(*chunk_x, *chunk_z).0 != [region_x, region_z]);
+    }
+}
+
+/// Splits absolute chunk coordinates into the region coordinates and the coordinates within that region.
+pub fn split_chunk_coordinates(chunk_x: i32, chunk_z: i32) -> ([i32; 2], [u8; 2]) {
+    (
+        [chunk_x.div_euclid(CHUNKS_PER_AXIS as i32), chunk_z.div_euclid(CHUNKS_PER_AXIS as i32)],
+        [chunk_x.rem_euclid(CHUNKS_PER_AXIS as i32) as u8, chunk_z.rem_euclid(CHUNKS_PER_AXIS as i32) as u8]
+    )
+}
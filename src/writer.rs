@@ -0,0 +1,100 @@
+use std::io::{Seek, SeekFrom, Write};
+
+use crab_nbt::Nbt;
+
+use crate::{check_chunk_coordinates, get_chunk_index, ENTRY_SIZE, HEADER_SECTORS, LOCATION_SIZE_FACTOR, TABLE_SIZE};
+use crate::chunks::{encode_nbt, Chunk, CompressionFormat};
+use crate::container::RawChunk;
+use crate::external::{externalize_payload, ExternalChunkResolver};
+use crate::metadata::{ChunkTimestamp, LocationTableEntry, RegionHeader};
+
+/// Creates a new region file from scratch.
+/// Chunks are appended to the file in the order they are written.
+/// The header is only written once [`RegionFileWriter::finish`] is called,
+/// so dropping the writer before that leaves an invalid region file behind.
+pub struct RegionFileWriter<W: Write + Seek> {
+    writer: W,
+    compression: CompressionFormat,
+    header: RegionHeader,
+    // next unused sector (in 4096 bytes)
+    next_sector: u32,
+    external: Option<Box<dyn ExternalChunkResolver>>
+}
+impl<W: Write + Seek> RegionFileWriter<W> {
+    pub fn create(mut writer: W, compression: CompressionFormat) -> std::io::Result<Self> {
+        writer.seek(SeekFrom::Start(0))?;
+        // Reserve space for the header, it is filled in by finish
+        writer.write_all(&[0u8; 2 * TABLE_SIZE * ENTRY_SIZE])?;
+        Ok(RegionFileWriter {
+            writer,
+            compression,
+            header: RegionHeader::empty(),
+            next_sector: HEADER_SECTORS,
+            external: None
+        })
+    }
+
+    /// Sets the resolver used to store chunks that do not fit into the region file.
+    /// Without a resolver writing these chunks fails.
+    pub fn with_external_resolver(mut self, resolver: impl ExternalChunkResolver + 'static) -> Self {
+        self.external = Some(Box::new(resolver));
+        self
+    }
+
+    pub fn get_compression(&self) -> CompressionFormat {
+        self.compression
+    }
+
+    /// Writes the chunk into the region file.
+    /// Writing the same coordinates twice replaces the previous entry, but leaves its sectors unused.
+    pub fn write_chunk(&mut self, chunk_x: u8, chunk_z: u8, chunk: &Chunk, timestamp: ChunkTimestamp) -> std::io::Result<()> {
+        self.write_nbt(chunk_x, chunk_z, &chunk.data, timestamp)
+    }
+
+    /// Writes arbitrary NBT as the chunk at the specified position.
+    /// No validation is performed on the NBT structure.
+    pub fn write_nbt(&mut self, chunk_x: u8, chunk_z: u8, nbt: &Nbt, timestamp: ChunkTimestamp) -> std::io::Result<()> {
+        self.write_payload(chunk_x, chunk_z, encode_nbt(nbt, self.compression)?, timestamp)
+    }
+
+    /// Writes the compressed data unchanged, e.g. as returned by [`crate::RegionFileReader::get_raw_chunk`].
+    /// The compression format of the writer is ignored.
+    pub fn write_raw_chunk(&mut self, chunk_x: u8, chunk_z: u8, chunk: &RawChunk, timestamp: ChunkTimestamp) -> std::io::Result<()> {
+        self.write_payload(chunk_x, chunk_z, chunk.to_payload()?, timestamp)
+    }
+
+    fn write_payload(&mut self, chunk_x: u8, chunk_z: u8, payload: Vec<u8>, timestamp: ChunkTimestamp) -> std::io::Result<()> {
+        let (payload, sectors) = prepare_payload(payload, self.external.as_deref_mut(), chunk_x, chunk_z)?;
+
+        self.writer.seek(SeekFrom::Start(self.next_sector as u64 * LOCATION_SIZE_FACTOR as u64))?;
+        self.writer.write_all(&payload)?;
+        // Pad to the sector boundary so the file length is always a multiple of 4096
+        let padding = sectors as usize * LOCATION_SIZE_FACTOR - payload.len();
+        self.writer.write_all(&vec![0u8; padding])?;
+
+        let index = get_chunk_index(chunk_x, chunk_z);
+        self.header.location_table[index] = LocationTableEntry::new(self.next_sector, sectors);
+        self.header.timestamp_table[index] = timestamp;
+        self.next_sector += sectors as u32;
+        Ok(())
+    }
+
+    /// Writes the header and returns the underlying writer.
+    pub fn finish(mut self) -> std::io::Result<W> {
+        self.writer.seek(SeekFrom::Start(0))?;
+        self.header.write(&mut self.writer)?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Checks the coordinates and moves the payload to the resolver if it is too large.
+/// Returns the payload to store in the region file and the number of sectors it occupies.
+pub(crate) fn prepare_payload(
+    payload: Vec<u8>, external: Option<&mut (dyn ExternalChunkResolver + 'static)>, chunk_x: u8, chunk_z: u8
+) -> std::io::Result<(Vec<u8>, u8)> {
+    check_chunk_coordinates(chunk_x, chunk_z)?;
+    let payload = externalize_payload(payload, external, chunk_x, chunk_z)?;
+    let sectors = payload.len().div_ceil(LOCATION_SIZE_FACTOR) as u8;
+    Ok((payload, sectors))
+}
@@ -0,0 +1,53 @@
+#![cfg(feature = "tokio")]
+use std::io::Cursor;
+
+use crab_nbt::Nbt;
+use rusty_anvil::{AsyncRegionFileReader, RegionFileReader};
+use rusty_anvil::chunks::Chunk;
+
+#[tokio::test]
+async fn matches_reader() {
+    let data = include_bytes!("data/superflat-colored.mca");
+    let mut async_reader = AsyncRegionFileReader::create(Cursor::new(&data[..])).await.unwrap();
+    let mut reader = RegionFileReader::create(Cursor::new(&data[..])).unwrap();
+    assert_eq!(async_reader.get_timestamps().as_ref(), reader.get_timestamps().as_ref());
+
+    for ([x, z], expected) in reader.get_chunks() {
+        match expected {
+            Some(expected) => {
+                let expected = expected.unwrap().data;
+                assert_eq!(async_reader.get_chunk(x, z).await.unwrap().data, expected, "{x},{z} does not match");
+                assert_eq!(async_reader.get_chunk_blocking(x, z).await.unwrap().data, expected, "{x},{z} does not match");
+            },
+            None => assert!(async_reader.get_chunk(x, z).await.is_err(), "{x},{z} should not exist")
+        }
+    }
+}
+
+#[tokio::test]
+async fn chunk_bytes() {
+    let data = include_bytes!("data/superflat-colored.mca");
+    let mut async_reader = AsyncRegionFileReader::create(Cursor::new(&data[..])).await.unwrap();
+    let mut reader = RegionFileReader::create(Cursor::new(&data[..])).unwrap();
+    assert_eq!(async_reader.get_chunk_bytes(0, 31).await.unwrap(), reader.get_chunk_bytes(0, 31).unwrap());
+    assert!(async_reader.get_chunk_bytes(0, 0).await.is_err(), "0,0 should not exist");
+}
+
+#[tokio::test]
+async fn raw_chunk() {
+    let data = include_bytes!("data/superflat-colored.mca");
+    let mut async_reader = AsyncRegionFileReader::create(Cursor::new(&data[..])).await.unwrap();
+    let mut reader = RegionFileReader::create(Cursor::new(&data[..])).unwrap();
+    let raw = async_reader.get_raw_chunk(0, 31).await.unwrap();
+    assert_eq!(raw, reader.get_raw_chunk(0, 31).unwrap());
+    assert_eq!(raw.decode::<Chunk>().unwrap().data, reader.get_chunk(0, 31).unwrap().data);
+}
+
+#[tokio::test]
+async fn blocking_raw_nbt() {
+    let data = include_bytes!("data/superflat-colored.mca");
+    let mut async_reader = AsyncRegionFileReader::create(Cursor::new(&data[..])).await.unwrap();
+    let mut reader = RegionFileReader::create(Cursor::new(&data[..])).unwrap();
+    let nbt: Nbt = async_reader.get_chunk_blocking_as(0, 31).await.unwrap();
+    assert_eq!(nbt.write(), reader.get_chunk(0, 31).unwrap().data.write());
+}
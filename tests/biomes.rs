@@ -0,0 +1,87 @@
+use std::io::Cursor;
+
+use crab_nbt::{Nbt, NbtCompound, NbtTag};
+use rusty_anvil::{RegionFileReader, RegionFileWriter};
+use rusty_anvil::chunks::{Chunk, CompressionFormat};
+use rusty_anvil::error::ChunkLoadError;
+
+#[test]
+fn single_biome_sections() {
+    let mut reader = RegionFileReader::create(
+        Cursor::new(&include_bytes!("data/superflat-colored.mca")[..])).unwrap();
+    let chunk = reader.get_chunk(0, 31).unwrap();
+    let section = chunk.get_subchunk_at(0).unwrap().unwrap();
+    let biomes = section.biomes.expect("1.21 sections should have biomes");
+
+    assert_eq!(biomes.get_palette().len(), 1);
+    assert_eq!(biomes.get_biome(3, 3, 3), biomes.get_palette()[0]);
+    assert_eq!(biomes.into_iter().filter(|biome| *biome == biomes.get_palette()[0]).count(), 64);
+}
+
+/// Loads a chunk with a single section using the biome palette and data
+fn chunk_with_biomes(palette: &[&str], data: Option<Vec<i64>>) -> Chunk {
+    let mut biomes = NbtCompound::new();
+    biomes.put("palette".to_owned(), NbtTag::List(palette.iter().map(|name| (*name).into()).collect()));
+    if let Some(data) = data {
+        biomes.put("data".to_owned(), data);
+    }
+    let mut air = NbtCompound::new();
+    air.put("Name".to_owned(), "minecraft:air");
+    let mut block_states = NbtCompound::new();
+    block_states.put("palette".to_owned(), NbtTag::List(vec![NbtTag::Compound(air)]));
+    let mut section = NbtCompound::new();
+    section.put("Y".to_owned(), 0i8);
+    section.put("block_states".to_owned(), block_states);
+    section.put("biomes".to_owned(), biomes);
+
+    let mut root = NbtCompound::new();
+    root.put("DataVersion".to_owned(), 3955);
+    root.put("xPos".to_owned(), 0);
+    root.put("zPos".to_owned(), 0);
+    root.put("Status".to_owned(), "minecraft:full");
+    root.put("Heightmaps".to_owned(), NbtCompound::new());
+    root.put("sections".to_owned(), NbtTag::List(vec![NbtTag::Compound(section)]));
+
+    let mut writer = RegionFileWriter::create(Cursor::new(Vec::new()), CompressionFormat::Zlib).unwrap();
+    writer.write_nbt(0, 0, &Nbt::new(String::new(), root), 1).unwrap();
+    let mut reader = RegionFileReader::create(Cursor::new(writer.finish().unwrap().into_inner())).unwrap();
+    reader.get_chunk(0, 0).unwrap()
+}
+
+#[test]
+fn mixed_biomes() {
+    // 3 biomes use 2 bits per cell, so each long holds 32 cells
+    let cells: Vec<u64> = (0..64).map(|i| i % 3).collect();
+    let data: Vec<i64> = cells.chunks(32)
+        .map(|chunk| chunk.iter().enumerate().fold(0u64, |long, (i, cell)| long | cell << (2 * i)) as i64)
+        .collect();
+    let names = ["minecraft:plains", "minecraft:desert", "minecraft:ocean"];
+    let chunk = chunk_with_biomes(&names, Some(data));
+    let biomes = chunk.get_subchunk(0).unwrap().biomes.unwrap();
+
+    let mut count = 0;
+    for ([x, y, z], biome) in biomes.into_iter().with_coordinates() {
+        let i = x as usize + z as usize * 4 + y as usize * 16;
+        assert_eq!(biome, names[i % 3], "{x},{y},{z} does not match");
+        assert_eq!(biomes.get_biome(x, y, z), biome);
+        count += 1;
+    }
+    assert_eq!(count, 64);
+}
+
+#[test]
+fn single_biome_with_data() {
+    let chunk = chunk_with_biomes(&["minecraft:plains"], Some(vec![0; 4]));
+    let biomes = chunk.get_subchunk(0).unwrap().biomes.unwrap();
+    assert_eq!(biomes.get_biome(1, 2, 3), "minecraft:plains");
+    assert_eq!(biomes.into_iter().count(), 64);
+}
+
+#[test]
+fn biome_data_exceeds_palette() {
+    // Index 3 with 2 bits per cell, but only 3 palette entries
+    let chunk = chunk_with_biomes(&["minecraft:plains", "minecraft:desert", "minecraft:ocean"], Some(vec![-1; 2]));
+    assert!(matches!(chunk.get_subchunk(0), Err(ChunkLoadError::MalformedChunk(_))));
+    let chunk = chunk_with_biomes(&["minecraft:plains", "minecraft:desert", "minecraft:ocean"], Some(vec![0]));
+    assert!(matches!(chunk.get_subchunk(0), Err(ChunkLoadError::MalformedChunk(_))), "Biome data is too short");
+}
@@ -0,0 +1,109 @@
+use std::io::Cursor;
+
+use crab_nbt::{Nbt, NbtCompound, NbtTag};
+use rusty_anvil::{RegionFileReader, RegionFileWriter};
+use rusty_anvil::chunks::{Chunk, CompressionFormat};
+
+fn block_entity(id: &str, [x, y, z]: [i32; 3]) -> NbtTag {
+    let mut compound = NbtCompound::new();
+    compound.put("id".to_owned(), id);
+    compound.put("x".to_owned(), x);
+    compound.put("y".to_owned(), y);
+    compound.put("z".to_owned(), z);
+    compound.put("keepPacked".to_owned(), 0i8);
+    compound.put("CustomName".to_owned(), format!("{id} at {x} {y} {z}"));
+    NbtTag::Compound(compound)
+}
+
+fn load(nbt: Nbt) -> Chunk {
+    let mut writer = RegionFileWriter::create(Cursor::new(Vec::new()), CompressionFormat::Zlib).unwrap();
+    writer.write_nbt(0, 31, &nbt, 1).unwrap();
+    let mut reader = RegionFileReader::create(Cursor::new(writer.finish().unwrap().into_inner())).unwrap();
+    reader.get_chunk(0, 31).unwrap()
+}
+
+/// The superflat chunk at (0,-1) with a chest and a sign added
+fn superflat_with_block_entities() -> Chunk {
+    let mut reader = RegionFileReader::create(
+        Cursor::new(&include_bytes!("data/superflat-colored.mca")[..])).unwrap();
+    let nbt = reader.get_chunk(0, 31).unwrap().data;
+    let mut root: NbtCompound = nbt.root_tag.child_tags.into_iter()
+        .filter(|(key, _)| key != "block_entities")
+        .collect();
+    root.put("block_entities".to_owned(), NbtTag::List(vec![
+        block_entity("minecraft:chest", [3, -61, -5]),
+        block_entity("minecraft:sign", [15, -62, -16]),
+    ]));
+    load(Nbt::new(nbt.name, root))
+}
+
+#[test]
+fn iterate_block_entities() {
+    let chunk = superflat_with_block_entities();
+    let block_entities: Vec<_> = chunk.get_block_entities().unwrap()
+        .collect::<Result<_, _>>().unwrap();
+
+    assert_eq!(block_entities.len(), 2);
+    assert_eq!(block_entities[0].id, "minecraft:chest");
+    assert_eq!(block_entities[0].position, [3, -61, -5]);
+    assert_eq!(block_entities[1].id, "minecraft:sign");
+    assert_eq!(block_entities[1].position, [15, -62, -16]);
+    // Only the contents remain, not the header
+    let keys: Vec<_> = block_entities[0].get_data().map(|(key, _)| key.as_str()).collect();
+    assert_eq!(keys, ["CustomName"]);
+    assert_eq!(
+        block_entities[0].get("CustomName").and_then(NbtTag::extract_string).map(String::as_str),
+        Some("minecraft:chest at 3 -61 -5")
+    );
+    assert!(block_entities[0].get("id").is_none());
+}
+
+#[test]
+fn block_entity_lookup() {
+    let chunk = superflat_with_block_entities();
+
+    let sign = chunk.get_block_entity_at(15, -62, -16).unwrap().unwrap();
+    assert_eq!(sign.id, "minecraft:sign");
+    assert!(chunk.get_block_entity_at(15, -62, -15).unwrap().is_none());
+
+    let (block, chest) = chunk.get_block_with_entity_at(3, -61, -5).unwrap().unwrap();
+    assert_eq!(block, chunk.get_block_at(3, -61, -5).unwrap().unwrap());
+    assert_eq!(chest.unwrap().id, "minecraft:chest");
+    let (_, none) = chunk.get_block_with_entity_at(4, -61, -5).unwrap().unwrap();
+    assert!(none.is_none());
+    // Outside of the chunk
+    assert!(chunk.get_block_with_entity_at(16, -61, -5).unwrap().is_none());
+}
+
+#[test]
+fn tile_entities_in_level() {
+    let mut level = NbtCompound::new();
+    level.put("xPos".to_owned(), 0);
+    level.put("zPos".to_owned(), -1);
+    level.put("Status".to_owned(), "full");
+    level.put("Heightmaps".to_owned(), NbtCompound::new());
+    level.put("Sections".to_owned(), NbtTag::List(Vec::new()));
+    level.put("TileEntities".to_owned(), NbtTag::List(vec![block_entity("minecraft:furnace", [1, 2, -3])]));
+    let mut root = NbtCompound::new();
+    root.put("DataVersion".to_owned(), 2230);
+    root.put("Level".to_owned(), level);
+    let chunk = load(Nbt::new(String::new(), root));
+
+    let furnace = chunk.get_block_entity_at(1, 2, -3).unwrap().unwrap();
+    assert_eq!(furnace.id, "minecraft:furnace");
+    assert_eq!(chunk.get_block_entities().unwrap().count(), 1);
+}
+
+#[test]
+fn missing_block_entities() {
+    let mut root = NbtCompound::new();
+    root.put("DataVersion".to_owned(), 3955);
+    root.put("xPos".to_owned(), 0);
+    root.put("zPos".to_owned(), -1);
+    root.put("Status".to_owned(), "minecraft:full");
+    root.put("Heightmaps".to_owned(), NbtCompound::new());
+    root.put("sections".to_owned(), NbtTag::List(Vec::new()));
+    let chunk = load(Nbt::new(String::new(), root));
+
+    assert_eq!(chunk.get_block_entities().unwrap().count(), 0);
+}
@@ -0,0 +1,70 @@
+use std::collections::HashMap;
+
+use crab_nbt::NbtTag;
+use rusty_anvil::chunks::sections::{BlockState, OwnedBlockState};
+
+#[test]
+fn parse_and_format() {
+    let stairs: OwnedBlockState = "minecraft:oak_stairs[facing=north,half=top]".parse().unwrap();
+    assert_eq!(stairs.name, "minecraft:oak_stairs");
+    assert_eq!(stairs.get_property("facing"), Some("north"));
+    assert_eq!(stairs.to_string(), "minecraft:oak_stairs[facing=north,half=top]");
+
+    // Properties are sorted and the namespace defaults to minecraft
+    let stairs: OwnedBlockState = "oak_stairs[ half=top , facing=north ]".parse().unwrap();
+    assert_eq!(stairs.to_string(), "minecraft:oak_stairs[facing=north,half=top]");
+
+    let stone: OwnedBlockState = "stone[]".parse().unwrap();
+    assert_eq!(stone.to_string(), "minecraft:stone");
+    assert_eq!("mod:custom".parse::<OwnedBlockState>().unwrap().name, "mod:custom");
+}
+
+#[test]
+fn invalid_syntax() {
+    for invalid in ["", "[facing=north]", "oak_stairs[facing=north", "oak_stairs[facing]", "oak_stairs[facing=]",
+        "oak_stairs[facing=north,facing=south]", "oak_stairs[facing=north,]", "oak stairs",
+        "a:b:c", "minecraft:", ":stone", "oak_stairs[fa cing=north]", "oak_stairs[facing=no rth]"] {
+        assert!(invalid.parse::<OwnedBlockState>().is_err(), "{invalid:?} should not parse");
+    }
+}
+
+#[test]
+fn typed_properties() {
+    let wheat: OwnedBlockState = "wheat[age=7]".parse().unwrap();
+    assert_eq!(wheat.get_int("age"), Some(7));
+    assert_eq!(wheat.get_bool("age"), None);
+    let slab = OwnedBlockState::new("minecraft:oak_slab").with_property("waterlogged", true).with_property("type", "top");
+    assert_eq!(slab.get_bool("waterlogged"), Some(true));
+    assert_eq!(slab.get_property("type"), Some("top"));
+    assert_eq!(slab.get_int("type"), None);
+    assert_eq!(slab.get_property("missing"), None);
+}
+
+#[test]
+fn equality_and_hashing() {
+    let a: OwnedBlockState = "oak_stairs[facing=north,half=top]".parse().unwrap();
+    let b: OwnedBlockState = "oak_stairs[half=top,facing=north]".parse().unwrap();
+    let c: OwnedBlockState = "oak_stairs[half=bottom,facing=north]".parse().unwrap();
+    assert_eq!(a, b);
+    assert_ne!(a, c);
+
+    let mut counts = HashMap::new();
+    for state in [a, b, c] {
+        *counts.entry(state).or_insert(0) += 1;
+    }
+    assert_eq!(counts.len(), 2);
+}
+
+#[test]
+fn borrowed_block_states() {
+    let name = "minecraft:oak_stairs".to_owned();
+    let properties = vec![("half".to_owned(), NbtTag::from("top")), ("facing".to_owned(), NbtTag::from("north"))];
+    let borrowed = BlockState { name: &name, properties: &properties };
+    // Borrowed states keep the stored order
+    assert_eq!(borrowed.to_string(), "minecraft:oak_stairs[half=top,facing=north]");
+
+    let owned = OwnedBlockState::from(borrowed);
+    assert_eq!(owned, "oak_stairs[facing=north,half=top]".parse::<OwnedBlockState>().unwrap());
+    assert_eq!(owned, borrowed);
+    assert_ne!(OwnedBlockState::new("minecraft:oak_stairs"), borrowed);
+}
@@ -0,0 +1,38 @@
+use std::io::Cursor;
+
+use rusty_anvil::RegionFileReader;
+use rusty_anvil::error::ChunkLoadError;
+
+#[test]
+fn block_at_absolute_coordinates() {
+    let mut reader = RegionFileReader::create(
+        Cursor::new(&include_bytes!("data/superflat-colored.mca")[..])).unwrap();
+    // This chunk lies at negative z coordinates (region 0,-1)
+    let chunk = reader.get_chunk(0, 31).unwrap();
+    assert_eq!(chunk.get_position().unwrap(), [0, -1]);
+
+    for section in chunk.get_subchunks().unwrap() {
+        let section = match section {
+            // Sections at the edge of the world only contain light data
+            Err(ChunkLoadError::EmptySection) => continue,
+            section => section.unwrap()
+        };
+        for ([x, y, z], block) in (&section.blocks).into_iter().with_coordinates() {
+            let absolute = [x as i32, section.y as i32 * 16 + y as i32, z as i32 - 16];
+            let found = chunk.get_block_at(absolute[0], absolute[1], absolute[2]).unwrap();
+            assert_eq!(found.as_ref(), Some(block), "{absolute:?} does not match the section");
+        }
+    }
+}
+
+#[test]
+fn block_at_outside_of_chunk() {
+    let mut reader = RegionFileReader::create(
+        Cursor::new(&include_bytes!("data/superflat-colored.mca")[..])).unwrap();
+    let chunk = reader.get_chunk(0, 31).unwrap();
+    assert!(chunk.get_block_at(0, 0, 0).unwrap().is_none(), "Position is in the neighbouring chunk");
+    assert!(chunk.get_block_at(16, 0, -1).unwrap().is_none(), "Position is in the neighbouring chunk");
+    assert!(chunk.get_block_at(0, 1000, -1).unwrap().is_none(), "Position is above the world");
+    assert!(chunk.get_block_at(0, -1000, -1).unwrap().is_none(), "Position is below the world");
+    assert!(chunk.get_block_at(0, -64, -1).unwrap().is_some());
+}
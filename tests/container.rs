@@ -0,0 +1,108 @@
+use std::io::Cursor;
+
+use crab_nbt::{Nbt, NbtCompound};
+use rusty_anvil::{RegionFile, RegionFileReader, RegionFileWriter, SliceRegionReader};
+use rusty_anvil::chunks::CompressionFormat;
+use rusty_anvil::container::ChunkData;
+use rusty_anvil::error::{ChunkLoadError, ChunkLoadError::MalformedChunk};
+
+const REGION: &[u8] = include_bytes!("data/superflat-colored.mca");
+
+/// Data of a hypothetical mod, stored in its own Anvil container
+struct Claim {
+    owner: String,
+    trusted: i32
+}
+impl ChunkData for Claim {
+    fn from_nbt(nbt: Nbt) -> Result<Self, ChunkLoadError> {
+        Ok(Claim {
+            owner: nbt.get_string("owner").ok_or_else(|| MalformedChunk("Claim has no owner".to_owned()))?.clone(),
+            trusted: nbt.get_int("trusted").unwrap_or(0)
+        })
+    }
+}
+
+fn claims() -> Vec<u8> {
+    let mut claim = NbtCompound::new();
+    claim.put("owner".to_owned(), "Steve");
+    claim.put("trusted".to_owned(), 3);
+    let mut writer = RegionFileWriter::create(Cursor::new(Vec::new()), CompressionFormat::Lz4).unwrap();
+    writer.write_nbt(7, 9, &Nbt::new(String::new(), claim), 1).unwrap();
+    writer.finish().unwrap().into_inner()
+}
+
+#[test]
+fn custom_data() {
+    let mut reader = RegionFileReader::create(Cursor::new(claims())).unwrap();
+    let claim: Claim = reader.get_chunk_as(7, 9).unwrap();
+    assert_eq!((claim.owner.as_str(), claim.trusted), ("Steve", 3));
+    assert!(matches!(reader.get_chunk_as::<Claim>(7, 8), Err(ChunkLoadError::ChunkDoesNotExist)));
+
+    let slice = SliceRegionReader::create(claims()).unwrap();
+    assert_eq!(slice.get_chunk_as::<Claim>(7, 9).unwrap().owner, "Steve");
+
+    let mut region = RegionFile::open(Cursor::new(claims()), CompressionFormat::Zlib).unwrap();
+    assert_eq!(region.get_chunk_as::<Claim>(7, 9).unwrap().trusted, 3);
+}
+
+#[test]
+fn raw_nbt() {
+    let mut reader = RegionFileReader::create(Cursor::new(REGION)).unwrap();
+    let nbt: Nbt = reader.get_chunk_as(0, 31).unwrap();
+    assert_eq!(nbt.get_int("DataVersion"), Some(3955));
+    assert_eq!(nbt.write(), reader.get_chunk(0, 31).unwrap().data.write());
+}
+
+#[test]
+fn raw_bytes() {
+    let mut reader = RegionFileReader::create(Cursor::new(REGION)).unwrap();
+    let bytes = reader.get_chunk_bytes(0, 31).unwrap();
+    assert_eq!(bytes, reader.get_chunk(0, 31).unwrap().data.write());
+
+    let slice = SliceRegionReader::create(REGION).unwrap();
+    assert_eq!(slice.get_chunk_bytes(0, 31).unwrap().as_ref(), &bytes[..]);
+
+    let mut region = RegionFile::open(Cursor::new(REGION.to_vec()), CompressionFormat::Zlib).unwrap();
+    assert_eq!(region.get_chunk_bytes(0, 31).unwrap(), bytes);
+}
+
+#[test]
+fn slots() {
+    let mut reader = RegionFileReader::create(Cursor::new(claims())).unwrap();
+    let slot = reader.read_slot(7, 9).unwrap();
+    assert_eq!(slot.get_payload().len(), 4096);
+    assert_eq!(slot.get_payload()[4], CompressionFormat::Lz4 as u8);
+    assert_eq!(slot.decode::<Claim>().unwrap().owner, "Steve");
+    assert_eq!(slot.into_raw().unwrap().compression, CompressionFormat::Lz4 as u8);
+    assert!(matches!(reader.read_slot(7, 8), Err(ChunkLoadError::ChunkDoesNotExist)));
+    assert!(matches!(reader.read_slot(39, 8), Err(ChunkLoadError::IOError(_))), "Coordinates are out of range");
+}
+
+#[test]
+fn iterate_custom_data() {
+    let mut reader = RegionFileReader::create(Cursor::new(claims())).unwrap();
+    let claims: Vec<_> = reader.get_chunks_as::<Claim>()
+        .filter_map(|(coordinates, claim)| claim.map(|claim| (coordinates, claim.unwrap().owner)))
+        .collect();
+    assert_eq!(claims, vec![([7, 9], "Steve".to_owned())]);
+
+    let slice = SliceRegionReader::create(REGION).unwrap();
+    let mut reader = RegionFileReader::create(Cursor::new(REGION)).unwrap();
+    for (([x, z], nbt), (_, chunk)) in slice.get_chunks_as::<Nbt>().zip(reader.get_chunks()) {
+        assert_eq!(nbt.is_some(), chunk.is_some(), "{x},{z} does not match");
+    }
+}
+
+#[test]
+fn shared_header() {
+    let reader = RegionFileReader::create(Cursor::new(REGION)).unwrap();
+    let slice = SliceRegionReader::create(REGION).unwrap();
+    let region = RegionFile::open(Cursor::new(REGION.to_vec()), CompressionFormat::Zlib).unwrap();
+    assert_eq!(reader.get_timestamps().as_ref(), slice.get_timestamps().as_ref());
+    assert_eq!(reader.get_timestamp(0, 31), region.get_timestamp(0, 31));
+    assert_eq!(reader.get_timestamp(32, 30), None, "Coordinates are out of range");
+    assert_eq!(
+        reader.get_location_table().present_chunks().collect::<Vec<_>>(),
+        region.get_location_table().present_chunks().collect::<Vec<_>>()
+    );
+}
@@ -0,0 +1,142 @@
+use std::io::Cursor;
+
+use crab_nbt::{Nbt, NbtCompound, NbtTag};
+use rusty_anvil::RegionFileReader;
+use rusty_anvil::chunks::{Chunk, CompressionFormat};
+use rusty_anvil::chunks::editing::OwnedSection;
+use rusty_anvil::chunks::sections::OwnedBlockState;
+use rusty_anvil::error::ChunkLoadError;
+
+fn states(count: usize) -> Vec<OwnedBlockState> {
+    (0..count).map(|i| OwnedBlockState::new(format!("minecraft:test_{i}"))).collect()
+}
+
+fn superflat() -> Chunk {
+    let mut reader = RegionFileReader::create(
+        Cursor::new(&include_bytes!("data/superflat-colored.mca")[..])).unwrap();
+    reader.get_chunk(0, 31).unwrap()
+}
+
+#[test]
+fn grow_palette() {
+    let chunk = superflat();
+    let mut section = chunk.get_owned_section(-4).unwrap().unwrap();
+    let original = chunk.get_subchunk_at(-4).unwrap().unwrap();
+    for x in 0..16 {
+        for z in 0..16 {
+            assert_eq!(*section.get_block(x, 3, z), *original.blocks.get_block(x, 3, z));
+        }
+    }
+
+    // 20 new block states need 5 bits per block
+    let states = states(20);
+    for (i, state) in states.iter().enumerate() {
+        section.set_block(i as u8 % 16, 15, i as u8 / 16, state);
+    }
+    for (i, state) in states.iter().enumerate() {
+        assert_eq!(section.get_block(i as u8 % 16, 15, i as u8 / 16), state);
+    }
+    // Blocks that weren't touched remain after re-packing
+    assert_eq!(*section.get_block(7, 3, 7), *original.blocks.get_block(7, 3, 7));
+
+    let mut chunk = superflat();
+    chunk.set_section(section).unwrap();
+    let chunk = Chunk::read(&chunk.write(CompressionFormat::Zlib).unwrap()).unwrap();
+    assert_eq!(states[4], chunk.get_block_at(4, -49, -16).unwrap().unwrap());
+    assert_eq!(states[19], chunk.get_block_at(3, -49, -15).unwrap().unwrap());
+    assert_eq!(chunk.get_block_at(7, -61, -9).unwrap(), superflat().get_block_at(7, -61, -9).unwrap());
+}
+
+#[test]
+fn compact_palette() {
+    let states = states(3);
+    let mut section = OwnedSection::new(2, &states[0]);
+    assert_eq!(section.get_palette().len(), 1);
+    section.set_block(1, 2, 3, &states[1]);
+    section.set_block(4, 5, 6, &states[2]);
+    section.set_block(1, 2, 3, &states[0]);
+    assert_eq!(section.get_palette().len(), 3);
+
+    section.compact();
+    assert_eq!(section.get_palette(), [states[0].clone(), states[2].clone()]);
+    assert_eq!(section.get_block(4, 5, 6), &states[2]);
+    assert_eq!(section.get_block(1, 2, 3), &states[0]);
+
+    // A single block state doesn't need any data
+    section.fill(&states[1]);
+    let nbt = section.to_nbt();
+    let block_states = nbt.get_compound("block_states").unwrap();
+    assert_eq!(block_states.get_list("palette").unwrap().len(), 1);
+    assert!(block_states.get_long_array("data").is_none());
+}
+
+#[test]
+fn properties_are_kept() {
+    let stairs: OwnedBlockState = "oak_stairs[facing=north,half=top]".parse().unwrap();
+    let mut section = OwnedSection::new(0, OwnedBlockState::new("minecraft:air"));
+    section.set_block(0, 0, 0, &stairs);
+    // The order of properties doesn't matter when looking up palette entries
+    section.set_block(1, 0, 0, OwnedBlockState::new("minecraft:oak_stairs").with_property("half", "top").with_property("facing", "north"));
+    assert_eq!(section.get_palette().len(), 2);
+
+    let nbt = section.to_nbt();
+    let entry = nbt.get_compound("block_states").unwrap().get_list("palette").unwrap()[1].extract_compound().unwrap();
+    assert_eq!(entry.get_string("Name").map(String::as_str), Some("minecraft:oak_stairs"));
+    assert_eq!(entry.get_compound("Properties").unwrap().get_string("half").map(String::as_str), Some("top"));
+    assert_eq!(section.get_block(1, 0, 0), &stairs);
+}
+
+/// Chunks from 1.15 store sections without nesting and span values across longs
+#[test]
+fn older_format() {
+    let mut level = NbtCompound::new();
+    level.put("xPos".to_owned(), 0);
+    level.put("zPos".to_owned(), 0);
+    level.put("Status".to_owned(), "full");
+    level.put("Heightmaps".to_owned(), NbtCompound::new());
+    level.put("Sections".to_owned(), NbtTag::List(Vec::new()));
+    let mut root = NbtCompound::new();
+    root.put("DataVersion".to_owned(), 2230);
+    root.put("Level".to_owned(), level);
+    let mut chunk = Chunk::read(&payload(root)).unwrap();
+
+    let states = states(17);
+    let mut section = OwnedSection::new(1, &states[0]);
+    for i in 0..4096u16 {
+        section.set_block((i % 16) as u8, (i / 256) as u8, (i / 16 % 16) as u8, &states[i as usize % 17]);
+    }
+    chunk.set_section(section).unwrap();
+
+    let chunk = Chunk::read(&chunk.write(CompressionFormat::Gzip).unwrap()).unwrap();
+    let nbt = chunk.data.get_compound("Level").unwrap().get_list("Sections").unwrap()[0].extract_compound().unwrap();
+    assert!(nbt.get_compound("block_states").is_none());
+    // 4096 values of 5 bits
+    assert_eq!(nbt.get_long_array("BlockStates").unwrap().len(), 320);
+    for i in 0..4096 {
+        let block = chunk.get_block_at(i % 16, 16 + i / 256, i / 16 % 16).unwrap().unwrap();
+        assert_eq!(states[i as usize % 17], block);
+    }
+}
+
+#[test]
+fn legacy_chunks_are_read_only() {
+    let mut level = NbtCompound::new();
+    level.put("xPos".to_owned(), 0);
+    level.put("zPos".to_owned(), 0);
+    level.put("TerrainPopulated".to_owned(), 1i8);
+    level.put("Sections".to_owned(), NbtTag::List(Vec::new()));
+    let mut root = NbtCompound::new();
+    root.put("Level".to_owned(), level);
+    let mut chunk = Chunk::read(&payload(root)).unwrap();
+
+    let result = chunk.set_section(OwnedSection::new(0, OwnedBlockState::new("minecraft:air")));
+    assert!(matches!(result, Err(ChunkLoadError::UnsupportedFormat)));
+}
+
+fn payload(root: NbtCompound) -> Vec<u8> {
+    let data = Nbt::new(String::new(), root).write();
+    let mut payload = (data.len() as u32 + 1).to_be_bytes().to_vec();
+    payload.push(CompressionFormat::Uncompressed as u8);
+    payload.extend_from_slice(&data);
+    payload
+}
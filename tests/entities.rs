@@ -0,0 +1,71 @@
+use std::io::Cursor;
+
+use crab_nbt::{Nbt, NbtCompound, NbtTag};
+use rusty_anvil::{RegionFileReader, RegionFileWriter};
+use rusty_anvil::chunks::CompressionFormat;
+use rusty_anvil::chunks::version::DataVersion;
+use rusty_anvil::error::ChunkLoadError;
+
+fn pig(uuid: [i32; 4], x: f64, name: Option<&str>) -> NbtTag {
+    let mut compound = NbtCompound::new();
+    compound.put("id".to_owned(), "minecraft:pig");
+    compound.put("UUID".to_owned(), NbtTag::IntArray(uuid.to_vec()));
+    compound.put("Pos".to_owned(), NbtTag::List(vec![x.into(), 64.0.into(), (-12.5).into()]));
+    compound.put("Motion".to_owned(), NbtTag::List(vec![0.0.into(), (-0.0784).into(), 0.0.into()]));
+    compound.put("Rotation".to_owned(), NbtTag::List(vec![90f32.into(), 0f32.into()]));
+    compound.put("Health".to_owned(), 10f32);
+    if let Some(name) = name {
+        compound.put("CustomName".to_owned(), format!("\"{name}\""));
+    }
+    NbtTag::Compound(compound)
+}
+
+fn entity_region() -> RegionFileReader<Cursor<Vec<u8>>> {
+    let mut root = NbtCompound::new();
+    root.put("DataVersion".to_owned(), 3955);
+    root.put("Position".to_owned(), NbtTag::IntArray(vec![2, -1]));
+    root.put("Entities".to_owned(), NbtTag::List(vec![
+        pig([0x12345678, -0x6543211, 0x0bad_cafe, -1], 34.5, Some("Wilbur")),
+        pig([0, 0, 0, 1], 40.0, None),
+    ]));
+    let mut writer = RegionFileWriter::create(Cursor::new(Vec::new()), CompressionFormat::Zlib).unwrap();
+    writer.write_nbt(2, 31, &Nbt::new(String::new(), root), 1).unwrap();
+    RegionFileReader::create(Cursor::new(writer.finish().unwrap().into_inner())).unwrap()
+}
+
+#[test]
+fn read_entities() {
+    let mut region = entity_region();
+    let chunk = region.get_entity_chunk(2, 31).unwrap();
+    assert_eq!(chunk.data_version, Some(DataVersion(3955)));
+    assert_eq!(chunk.get_position(), [2, -1]);
+
+    let entities: Vec<_> = chunk.get_entities().collect::<Result<_, _>>().unwrap();
+    assert_eq!(entities.len(), 2);
+    let wilbur = entities[0];
+    assert_eq!(wilbur.id, "minecraft:pig");
+    assert_eq!(wilbur.uuid, 0x12345678_f9abcdef_0badcafe_ffffffff);
+    assert_eq!(wilbur.get_uuid_string(), "12345678-f9ab-cdef-0bad-cafeffffffff");
+    assert_eq!(wilbur.position, [34.5, 64.0, -12.5]);
+    assert_eq!(wilbur.motion, [0.0, -0.0784, 0.0]);
+    assert_eq!(wilbur.rotation, [90.0, 0.0]);
+    assert_eq!(wilbur.custom_name.and_then(NbtTag::extract_string).map(String::as_str), Some("\"Wilbur\""));
+    assert_eq!(wilbur.nbt.get_float("Health"), Some(10.0));
+
+    assert_eq!(entities[1].uuid, 1);
+    assert!(entities[1].custom_name.is_none());
+}
+
+#[test]
+fn entity_chunks_are_not_terrain() {
+    let mut region = entity_region();
+    assert!(matches!(region.get_chunk(2, 31), Err(ChunkLoadError::MalformedChunk(_))));
+    assert!(matches!(region.get_entity_chunk(3, 31), Err(ChunkLoadError::ChunkDoesNotExist)));
+}
+
+#[test]
+fn terrain_chunks_are_not_entities() {
+    let mut reader = RegionFileReader::create(
+        Cursor::new(&include_bytes!("data/superflat-colored.mca")[..])).unwrap();
+    assert!(matches!(reader.get_entity_chunk(0, 31), Err(ChunkLoadError::MalformedChunk(_))));
+}
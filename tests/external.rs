@@ -0,0 +1,67 @@
+use std::io::Cursor;
+use std::path::Path;
+
+use crab_nbt::NbtTag;
+use rusty_anvil::{RegionFile, RegionFileReader, RegionFileWriter};
+use rusty_anvil::chunks::CompressionFormat;
+use rusty_anvil::error::ChunkLoadError;
+use rusty_anvil::external::{DirectoryResolver, MemoryResolver};
+
+fn oversized_chunk() -> rusty_anvil::chunks::Chunk {
+    let mut reader = RegionFileReader::create(
+        Cursor::new(&include_bytes!("data/superflat-colored.mca")[..])).unwrap();
+    let mut chunk = reader.get_chunk(0, 31).unwrap();
+    // Uncompressed, this is well beyond the 255 sectors available in a region file
+    chunk.data.root_tag.put("Padding".to_owned(), NbtTag::from(&vec![1u8; 1_500_000][..]));
+    chunk
+}
+
+#[test]
+fn write_and_read_external() {
+    let chunk = oversized_chunk();
+    let resolver = MemoryResolver::new();
+    let mut writer = RegionFileWriter::create(Cursor::new(Vec::new()), CompressionFormat::Uncompressed)
+        .unwrap()
+        .with_external_resolver(resolver.clone());
+    writer.write_chunk(4, 2, &chunk, 1).unwrap();
+    let file = writer.finish().unwrap().into_inner();
+    assert!(resolver.contains(4, 2));
+    assert_eq!(file.len(), 3 * 4096, "Only a single sector should be used within the region file");
+
+    let mut reader = RegionFileReader::create(Cursor::new(&file[..])).unwrap();
+    assert!(matches!(reader.get_chunk(4, 2), Err(ChunkLoadError::ExternalChunkUnavailable)));
+
+    let mut reader = reader.with_external_resolver(resolver);
+    assert_eq!(reader.get_chunk(4, 2).unwrap().data, chunk.data);
+}
+
+#[test]
+fn oversized_without_resolver() {
+    let mut writer = RegionFileWriter::create(Cursor::new(Vec::new()), CompressionFormat::Uncompressed).unwrap();
+    assert!(writer.write_chunk(0, 0, &oversized_chunk(), 1).is_err());
+}
+
+#[test]
+fn replace_external_chunk() {
+    let resolver = MemoryResolver::new();
+    let mut region = RegionFile::open(
+        Cursor::new(include_bytes!("data/superflat-colored.mca").to_vec()),
+        CompressionFormat::Uncompressed
+    ).unwrap().with_external_resolver(resolver.clone());
+    let original = region.get_chunk(0, 31).unwrap();
+
+    region.write_chunk(0, 31, &oversized_chunk(), 1).unwrap();
+    assert!(resolver.contains(0, 31));
+    assert_eq!(region.get_chunk(0, 31).unwrap().data, oversized_chunk().data);
+
+    region.write_chunk(0, 31, &original, 1).unwrap();
+    assert!(!resolver.contains(0, 31), "External data should be removed once the chunk fits again");
+    assert_eq!(region.get_chunk(0, 31).unwrap().data, original.data);
+}
+
+#[test]
+fn directory_resolver_paths() {
+    let resolver = DirectoryResolver::for_region_file(Path::new("world/region/r.-1.2.mca")).unwrap();
+    assert_eq!(resolver.get_path(31, 0), Path::new("world/region/c.-1.64.mcc"));
+    assert!(DirectoryResolver::for_region_file(Path::new("world/region/level.dat")).is_none());
+}
@@ -0,0 +1,142 @@
+use std::io::Cursor;
+
+use crab_nbt::{Nbt, NbtCompound, NbtTag};
+use rusty_anvil::{RegionFileReader, RegionFileWriter};
+use rusty_anvil::chunks::{Chunk, ChunkStatus, CompressionFormat};
+use rusty_anvil::chunks::heightmaps::HeightmapType;
+use rusty_anvil::chunks::version::{BiomeFormat, DataVersion};
+
+const PALETTE_SIZE: usize = 20;
+
+/// Packs the values the way the game does, optionally spanning values across longs like before 1.16
+fn pack(values: &[u64], bits: usize, spanning: bool) -> Vec<i64> {
+    let mut longs = Vec::new();
+    let mut bit = 0;
+    for value in values {
+        if !spanning && bit % 64 + bits > 64 {
+            bit = bit.next_multiple_of(64);
+        }
+        let (index, offset) = (bit / 64, bit % 64);
+        longs.resize(longs.len().max((bit + bits).div_ceil(64)), 0u64);
+        longs[index] |= value << offset;
+        if offset + bits > 64 {
+            longs[index + 1] |= value >> (64 - offset);
+        }
+        bit += bits;
+    }
+    longs.into_iter().map(|long| long as i64).collect()
+}
+
+fn block_name(i: usize) -> String {
+    format!("minecraft:test_{}", i % PALETTE_SIZE)
+}
+
+/// A chunk in the layout used from 1.13 to 1.17
+fn level_chunk(data_version: i32) -> Chunk {
+    load(level_nbt(data_version, "full"))
+}
+
+fn load(nbt: Nbt) -> Chunk {
+    let mut writer = RegionFileWriter::create(Cursor::new(Vec::new()), CompressionFormat::Zlib).unwrap();
+    writer.write_nbt(3, 4, &nbt, 1).unwrap();
+    let region = writer.finish().unwrap().into_inner();
+    RegionFileReader::create(Cursor::new(region)).unwrap().get_chunk(3, 4).unwrap()
+}
+
+fn level_nbt(data_version: i32, status: &str) -> Nbt {
+    let spanning = data_version < 2529;
+    let palette = (0..PALETTE_SIZE).map(|i| {
+        let mut entry = NbtCompound::new();
+        entry.put("Name".to_owned(), block_name(i));
+        NbtTag::Compound(entry)
+    }).collect();
+    let indices: Vec<u64> = (0..4096).map(|i| (i * 7 % PALETTE_SIZE) as u64).collect();
+    let mut section = NbtCompound::new();
+    section.put("Y".to_owned(), 2i8);
+    section.put("Palette".to_owned(), NbtTag::List(palette));
+    section.put("BlockStates".to_owned(), pack(&indices, 5, spanning));
+    let mut light_section = NbtCompound::new();
+    light_section.put("Y".to_owned(), -1i8);
+
+    let heights: Vec<u64> = (0..256).map(|i| i as u64 * 2).collect();
+    let mut heightmaps = NbtCompound::new();
+    heightmaps.put("WORLD_SURFACE".to_owned(), pack(&heights, 9, spanning));
+
+    let mut level = NbtCompound::new();
+    level.put("xPos".to_owned(), 3);
+    level.put("zPos".to_owned(), 4);
+    level.put("Status".to_owned(), status);
+    level.put("Heightmaps".to_owned(), heightmaps);
+    level.put("Sections".to_owned(), NbtTag::List(vec![NbtTag::Compound(light_section), NbtTag::Compound(section)]));
+    let mut root = NbtCompound::new();
+    root.put("DataVersion".to_owned(), data_version);
+    root.put("Level".to_owned(), level);
+    Nbt::new(String::new(), root)
+}
+
+fn check_chunk(chunk: Chunk) {
+    assert_eq!(chunk.status, ChunkStatus::Full);
+    assert_eq!(chunk.get_biome_format(), BiomeFormat::Cells);
+    assert_eq!(chunk.get_position().unwrap(), [3, 4]);
+    assert!(chunk.get_subchunk_at(-1).unwrap().is_none());
+
+    let section = chunk.get_subchunk_at(2).unwrap().unwrap();
+    let mut count = 0;
+    for ([x, y, z], block) in (&section.blocks).into_iter().with_coordinates() {
+        let i = x as usize + z as usize * 16 + y as usize * 256;
+        assert_eq!(*block.name, block_name(i * 7), "{x},{y},{z} does not match");
+        assert_eq!(section.blocks.get_block(x, y, z), block);
+        count += 1;
+    }
+    assert_eq!(count, 4096);
+    assert_eq!(*chunk.get_block_at(48 + 5, 32 + 3, 64 + 9).unwrap().unwrap().name, block_name((5 + 9 * 16 + 3 * 256) * 7));
+
+    let heightmap = chunk.get_heightmap(HeightmapType::WorldSurface).unwrap();
+    let heights: Vec<_> = heightmap.into_iter().collect();
+    assert_eq!(heights, (0..256).map(|i| i * 2).collect::<Vec<u16>>());
+    assert_eq!(heightmap.get_at(15, 15), 510);
+}
+
+#[test]
+fn spanning_packing() {
+    // 1.15.2
+    check_chunk(level_chunk(2230));
+}
+
+#[test]
+fn aligned_packing_in_level() {
+    // 1.16.5
+    check_chunk(level_chunk(2586));
+}
+
+#[test]
+fn old_status_names() {
+    // 1.13.2
+    let chunk = load(level_nbt(1631, "postprocessed"));
+    assert_eq!(chunk.status, ChunkStatus::Full);
+    assert_eq!(chunk.get_biome_format(), BiomeFormat::Columns);
+    assert!(ChunkStatus::try_from("postprocessed").is_err(), "1.13 status names are only valid for 1.13 chunks");
+}
+
+#[test]
+fn data_versions() {
+    let mut reader = RegionFileReader::create(
+        Cursor::new(&include_bytes!("data/superflat-colored.mca")[..])).unwrap();
+    let chunk = reader.get_chunk(0, 31).unwrap();
+    assert_eq!(chunk.data_version, Some(DataVersion(3955)));
+    assert_eq!(chunk.data_version.unwrap().get_release_name(), Some("1.21.1"));
+    assert_eq!(chunk.get_biome_format(), BiomeFormat::SectionPalette);
+
+    assert_eq!(level_chunk(2230).data_version.unwrap().to_string(), "2230 (1.15.2)");
+    // 20w17a
+    assert_eq!(DataVersion(2529).get_release_name(), None);
+    assert_eq!(DataVersion(2529).get_latest_release(), Some("1.15.2"));
+    assert_eq!(DataVersion(100).get_latest_release(), None);
+    assert!(DataVersion::FLATTENING < DataVersion::REMOVED_LEVEL);
+}
+
+#[test]
+fn chunk_from_nbt() {
+    // Chunks don't need to come from a region file
+    check_chunk(Chunk::from_nbt(level_nbt(2230, "full")).unwrap());
+}
@@ -0,0 +1,138 @@
+use std::io::Cursor;
+
+use bytes::Bytes;
+use crab_nbt::{Nbt, NbtCompound, NbtTag};
+use rusty_anvil::{RegionFileReader, RegionFileWriter};
+use rusty_anvil::chunks::{ChunkStatus, CompressionFormat};
+use rusty_anvil::world::World;
+
+fn set_nibble(array: &mut [u8], index: usize, value: u8) {
+    array[index / 2] |= value << (index % 2 * 4);
+}
+
+fn chunk_nbt(level: NbtCompound, data_version: Option<i32>) -> Nbt {
+    let mut root = NbtCompound::new();
+    root.put("Level".to_owned(), level);
+    if let Some(version) = data_version {
+        root.put("DataVersion".to_owned(), version);
+    }
+    Nbt::new(String::new(), root)
+}
+
+/// A 1.12 chunk at (0, -1) with a single section at y 0
+fn anvil_chunk() -> Nbt {
+    let mut blocks = vec![0u8; 4096];
+    let mut data = vec![0u8; 2048];
+    let mut add = vec![0u8; 2048];
+    let index = |x: usize, y: usize, z: usize| y * 256 + z * 16 + x;
+    for i in 0..256 {
+        blocks[i] = 7;
+        blocks[256 + i] = 1;
+        set_nibble(&mut data, 256 + i, 1);
+    }
+    blocks[index(3, 5, 7)] = 35;
+    set_nibble(&mut data, index(3, 5, 7), 14);
+    blocks[index(4, 5, 7)] = 5;
+    set_nibble(&mut add, index(4, 5, 7), 1);
+
+    let mut section = NbtCompound::new();
+    section.put("Y".to_owned(), 0i8);
+    section.put("Blocks".to_owned(), Bytes::from(blocks));
+    section.put("Data".to_owned(), Bytes::from(data));
+    section.put("Add".to_owned(), Bytes::from(add));
+    section.put("SkyLight".to_owned(), Bytes::from(vec![0xffu8; 2048]));
+
+    let mut level = NbtCompound::new();
+    level.put("xPos".to_owned(), 0);
+    level.put("zPos".to_owned(), -1);
+    level.put("TerrainPopulated".to_owned(), true);
+    level.put("Sections".to_owned(), NbtTag::List(vec![NbtTag::Compound(section)]));
+    chunk_nbt(level, Some(1343))
+}
+
+/// A McRegion chunk at (1, 0) with a birch log lying along the x axis at (2, 70, 9)
+fn mcregion_chunk() -> Nbt {
+    let mut blocks = vec![0u8; 32768];
+    let mut data = vec![0u8; 16384];
+    let index = |x: usize, y: usize, z: usize| x * 2048 + z * 128 + y;
+    blocks[index(2, 70, 9)] = 17;
+    set_nibble(&mut data, index(2, 70, 9), 4 | 2);
+    let mut sky_light = vec![0u8; 16384];
+    set_nibble(&mut sky_light, index(2, 71, 9), 7);
+
+    let mut level = NbtCompound::new();
+    level.put("xPos".to_owned(), 1);
+    level.put("zPos".to_owned(), 0);
+    level.put("Blocks".to_owned(), Bytes::from(blocks));
+    level.put("Data".to_owned(), Bytes::from(data));
+    level.put("SkyLight".to_owned(), Bytes::from(sky_light));
+    chunk_nbt(level, None)
+}
+
+fn region_with(chunks: &[([u8; 2], Nbt)]) -> Vec<u8> {
+    let mut writer = RegionFileWriter::create(Cursor::new(Vec::new()), CompressionFormat::Zlib).unwrap();
+    for ([x, z], nbt) in chunks {
+        writer.write_nbt(*x, *z, nbt, 1).unwrap();
+    }
+    writer.finish().unwrap().into_inner()
+}
+
+#[test]
+fn pre_flattening_chunk() {
+    let region = region_with(&[([0, 31], anvil_chunk())]);
+    let mut reader = RegionFileReader::create(Cursor::new(region)).unwrap();
+    let chunk = reader.get_chunk(0, 31).unwrap();
+    assert_eq!(chunk.status, ChunkStatus::Full);
+    assert_eq!(chunk.get_position().unwrap(), [0, -1]);
+    assert!(chunk.get_heightmap(rusty_anvil::chunks::heightmaps::HeightmapType::WorldSurface).is_none());
+
+    assert_eq!(chunk.get_block_at(1, 0, -3).unwrap().unwrap().name, "minecraft:bedrock");
+    assert_eq!(chunk.get_block_at(1, 1, -3).unwrap().unwrap().name, "minecraft:granite");
+    assert_eq!(chunk.get_block_at(3, 5, -9).unwrap().unwrap().name, "minecraft:red_wool");
+    assert_eq!(chunk.get_block_at(1, 5, -9).unwrap().unwrap().name, "minecraft:air");
+    let modded = chunk.get_block_at(4, 5, -9).unwrap().unwrap();
+    assert_eq!(modded.name, "legacy:261");
+    assert_eq!(modded.properties, &vec![("data".to_owned(), NbtTag::String("0".to_owned()))]);
+    assert!(chunk.get_block_at(0, 16, -16).unwrap().is_none());
+
+    let section = chunk.get_subchunk_at(0).unwrap().unwrap();
+    assert_eq!(section.blocks.get_palette().len(), 5);
+    let granite_count = (&section.blocks).into_iter()
+        .filter(|block| block.name == "minecraft:granite")
+        .count();
+    assert_eq!(granite_count, 256);
+}
+
+#[test]
+fn mcregion_chunk_column() {
+    let region = region_with(&[([1, 0], mcregion_chunk())]);
+    let mut reader = RegionFileReader::create(Cursor::new(region)).unwrap();
+    let chunk = reader.get_chunk(1, 0).unwrap();
+    assert_eq!(chunk.status, ChunkStatus::Carvers);
+    assert_eq!(chunk.get_subchunks().unwrap().count(), 8);
+
+    let log = chunk.get_block_at(18, 70, 9).unwrap().unwrap();
+    assert_eq!(log.name, "minecraft:birch_log");
+    assert_eq!(log.properties, &vec![("axis".to_owned(), NbtTag::String("x".to_owned()))]);
+    assert_eq!(chunk.get_block_at(18, 71, 9).unwrap().unwrap().name, "minecraft:air");
+    assert!(chunk.get_block_at(18, 128, 9).unwrap().is_none());
+
+    let section = chunk.get_subchunk_at(4).unwrap().unwrap();
+    assert!(section.block_light.is_none());
+    let sky_light = section.sky_light.unwrap();
+    assert_eq!(sky_light.get(2, 7, 9), 7);
+    assert_eq!(sky_light.into_iter().map(u32::from).sum::<u32>(), 7);
+}
+
+#[test]
+fn mcregion_world() {
+    let path = std::env::temp_dir().join(format!("rusty-anvil-mcregion-{}", std::process::id()));
+    std::fs::create_dir_all(path.join("region")).unwrap();
+    std::fs::write(path.join("region/r.0.0.mcr"), region_with(&[([1, 0], mcregion_chunk())])).unwrap();
+
+    let mut world = World::open(&path).unwrap();
+    assert_eq!(world.get_block_at(18, 70, 9).unwrap().unwrap().name, "minecraft:birch_log");
+    let overworld = world.get_dimension(&rusty_anvil::world::DimensionId::Overworld).unwrap();
+    assert_eq!(overworld.list_regions().unwrap(), vec![[0, 0]]);
+    std::fs::remove_dir_all(path).unwrap();
+}
@@ -0,0 +1,89 @@
+use std::io::Cursor;
+
+use bytes::Bytes;
+use crab_nbt::{Nbt, NbtCompound, NbtTag};
+use rusty_anvil::{RegionFileReader, RegionFileWriter};
+use rusty_anvil::chunks::{Chunk, CompressionFormat};
+
+fn level_at(x: usize, y: usize, z: usize) -> u8 {
+    ((x + 3 * y + 7 * z) % 16) as u8
+}
+
+fn chunk_with_light() -> Chunk {
+    let mut block_light = vec![0u8; 2048];
+    for i in 0..4096 {
+        block_light[i / 2] |= level_at(i % 16, i / 256, i / 16 % 16) << (i % 2 * 4);
+    }
+    let mut air = NbtCompound::new();
+    air.put("Name".to_owned(), "minecraft:air");
+    let mut block_states = NbtCompound::new();
+    block_states.put("palette".to_owned(), NbtTag::List(vec![NbtTag::Compound(air)]));
+    let mut section = NbtCompound::new();
+    section.put("Y".to_owned(), 0i8);
+    section.put("block_states".to_owned(), block_states);
+    section.put("BlockLight".to_owned(), Bytes::from(block_light));
+
+    let mut root = NbtCompound::new();
+    root.put("DataVersion".to_owned(), 3955);
+    root.put("xPos".to_owned(), 0);
+    root.put("zPos".to_owned(), 0);
+    root.put("Status".to_owned(), "minecraft:full");
+    root.put("Heightmaps".to_owned(), NbtCompound::new());
+    root.put("sections".to_owned(), NbtTag::List(vec![NbtTag::Compound(section)]));
+
+    let mut writer = RegionFileWriter::create(Cursor::new(Vec::new()), CompressionFormat::Zlib).unwrap();
+    writer.write_nbt(0, 0, &Nbt::new(String::new(), root), 1).unwrap();
+    let mut reader = RegionFileReader::create(Cursor::new(writer.finish().unwrap().into_inner())).unwrap();
+    reader.get_chunk(0, 0).unwrap()
+}
+
+#[test]
+fn block_light() {
+    let chunk = chunk_with_light();
+    let section = chunk.get_subchunk(0).unwrap();
+    assert!(section.sky_light.is_none());
+    let light = section.block_light.expect("Section should have block light");
+
+    assert_eq!(light.get(1, 2, 3), level_at(1, 2, 3));
+    let mut count = 0;
+    for ([x, y, z], level) in light.into_iter().with_coordinates() {
+        assert_eq!(level, level_at(x as usize, y as usize, z as usize), "{x},{y},{z} does not match");
+        assert_eq!(light.get(x, y, z), level);
+        count += 1;
+    }
+    assert_eq!(count, 4096);
+}
+
+#[test]
+fn sky_light_in_region() {
+    let mut reader = RegionFileReader::create(
+        Cursor::new(&include_bytes!("data/superflat-colored.mca")[..])).unwrap();
+    let chunk = reader.get_chunk(0, 31).unwrap();
+    for section in chunk.get_subchunks().unwrap().filter_map(Result::ok) {
+        if let Some(sky_light) = section.sky_light {
+            assert_eq!(sky_light.into_iter().count(), 4096);
+            assert!(sky_light.into_iter().all(|level| level <= 15));
+        }
+    }
+}
+
+#[test]
+fn light_only_section() {
+    let mut section = NbtCompound::new();
+    section.put("Y".to_owned(), 5i8);
+    section.put("SkyLight".to_owned(), Bytes::from(vec![0xffu8; 2048]));
+    let mut root = NbtCompound::new();
+    root.put("DataVersion".to_owned(), 3955);
+    root.put("xPos".to_owned(), 0);
+    root.put("zPos".to_owned(), 0);
+    root.put("Status".to_owned(), "minecraft:full");
+    root.put("Heightmaps".to_owned(), NbtCompound::new());
+    root.put("sections".to_owned(), NbtTag::List(vec![NbtTag::Compound(section)]));
+    let chunk = Chunk::from_nbt(Nbt::new(String::new(), root)).unwrap();
+
+    assert!(chunk.get_subchunk_at(5).unwrap().is_none(), "Section has no blocks");
+    let light = chunk.get_light_at(5).unwrap().expect("Section should exist");
+    assert!(light.block_light.is_none());
+    assert!(light.sky_light.expect("Section should have sky light").into_iter().all(|level| level == 15));
+    assert!(chunk.get_light_at(6).unwrap().is_none());
+}
@@ -0,0 +1,45 @@
+use std::io::Cursor;
+
+use rusty_anvil::RegionFileReader;
+
+#[test]
+fn location_table() {
+    let data = include_bytes!("data/superflat-colored.mca");
+    let mut reader = RegionFileReader::create(Cursor::new(&data[..])).unwrap();
+    let present: Vec<_> = reader.get_location_table().present_chunks().collect();
+    assert_eq!(present.len(), 702);
+
+    for [x, z] in present {
+        let location = reader.get_location_table().get(x, z).unwrap().clone();
+        assert!(location.get_sector_offset() >= 2, "{x},{z} overlaps the header");
+        assert!(location.sectors().end as usize * 4096 <= data.len(), "{x},{z} lies outside of the file");
+
+        let info = reader.get_chunk_info(x, z).unwrap().expect("Chunk should exist");
+        assert_eq!(info.location, location);
+        assert!(info.length as usize + 4 <= location.get_sector_count() as usize * 4096);
+        assert!(info.get_compression_format().is_some());
+        assert!(!info.is_external());
+        reader.get_chunk(x, z).unwrap();
+    }
+}
+
+#[test]
+fn missing_chunk_info() {
+    let mut reader = RegionFileReader::create(
+        Cursor::new(&include_bytes!("data/superflat-colored.mca")[..])).unwrap();
+    let missing = (0..32).flat_map(|z| (0..32).map(move |x| [x, z]))
+        .find(|[x, z]| reader.get_location_table().get(*x, *z).unwrap().is_empty())
+        .expect("Test region should not be full");
+    assert!(reader.get_chunk_info(missing[0], missing[1]).unwrap().is_none());
+}
+
+#[test]
+fn out_of_bounds() {
+    let mut reader = RegionFileReader::create(
+        Cursor::new(&include_bytes!("data/superflat-colored.mca")[..])).unwrap();
+    // (32, 30) would otherwise alias (0, 31) and (40, 31) lies outside of the table
+    assert!(reader.get_location_table().get(32, 30).is_none());
+    assert!(reader.get_location_table().get(40, 31).is_none());
+    assert_eq!(reader.get_chunk_info(32, 30).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+    assert_eq!(reader.get_chunk_info(40, 31).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+}
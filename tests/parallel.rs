@@ -0,0 +1,24 @@
+#![cfg(feature = "rayon")]
+use std::collections::HashMap;
+use std::io::Cursor;
+
+use rayon::iter::ParallelIterator;
+use rusty_anvil::RegionFileReader;
+
+#[test]
+fn matches_sequential() {
+    let mut reader = RegionFileReader::create(
+        Cursor::new(&include_bytes!("data/superflat-colored.mca")[..])).unwrap();
+    let parallel: HashMap<_, _> = reader.par_get_chunks()
+        .map(|(coordinates, chunk)| (coordinates, chunk.unwrap().data))
+        .collect();
+
+    let mut count = 0;
+    for (coordinates, chunk) in reader.get_chunks() {
+        if let Some(chunk) = chunk {
+            count += 1;
+            assert_eq!(parallel.get(&coordinates), Some(&chunk.unwrap().data), "{coordinates:?} does not match");
+        }
+    }
+    assert_eq!(parallel.len(), count);
+}
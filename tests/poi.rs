@@ -0,0 +1,70 @@
+use std::io::Cursor;
+
+use crab_nbt::{Nbt, NbtCompound, NbtTag};
+use rusty_anvil::{RegionFileReader, RegionFileWriter};
+use rusty_anvil::chunks::CompressionFormat;
+use rusty_anvil::error::ChunkLoadError;
+
+fn record(poi_type: &str, position: [i32; 3], free_tickets: i32) -> NbtTag {
+    let mut compound = NbtCompound::new();
+    compound.put("type".to_owned(), poi_type);
+    compound.put("pos".to_owned(), NbtTag::IntArray(position.to_vec()));
+    compound.put("free_tickets".to_owned(), free_tickets);
+    NbtTag::Compound(compound)
+}
+
+fn section(valid: bool, records: Vec<NbtTag>) -> NbtCompound {
+    let mut compound = NbtCompound::new();
+    compound.put("Valid".to_owned(), valid);
+    compound.put("Records".to_owned(), NbtTag::List(records));
+    compound
+}
+
+fn poi_region() -> RegionFileReader<Cursor<Vec<u8>>> {
+    let mut sections = NbtCompound::new();
+    sections.put("-3".to_owned(), section(true, vec![
+        record("minecraft:nether_portal", [17, -40, 5], 0),
+        record("minecraft:nether_portal", [17, -39, 5], 0),
+    ]));
+    sections.put("4".to_owned(), section(false, vec![record("minecraft:home", [20, 70, 8], 1)]));
+    let mut root = NbtCompound::new();
+    root.put("DataVersion".to_owned(), 3955);
+    root.put("Sections".to_owned(), sections);
+    let mut writer = RegionFileWriter::create(Cursor::new(Vec::new()), CompressionFormat::Zlib).unwrap();
+    writer.write_nbt(1, 0, &Nbt::new(String::new(), root), 1).unwrap();
+    RegionFileReader::create(Cursor::new(writer.finish().unwrap().into_inner())).unwrap()
+}
+
+#[test]
+fn read_records() {
+    let chunk = poi_region().get_poi_chunk(1, 0).unwrap();
+
+    let sections: Vec<_> = chunk.get_sections().collect::<Result<_, _>>().unwrap();
+    assert_eq!(sections.len(), 2);
+    assert_eq!((sections[0].y, sections[0].valid), (-3, true));
+    assert_eq!((sections[1].y, sections[1].valid), (4, false));
+
+    let home = sections[1].get_records().next().unwrap().unwrap();
+    assert_eq!(home.poi_type, "minecraft:home");
+    assert_eq!(home.position, [20, 70, 8]);
+    assert_eq!(home.free_tickets, 1);
+}
+
+#[test]
+fn find_nether_portals() {
+    let chunk = poi_region().get_poi_chunk(1, 0).unwrap();
+    let portals: Vec<_> = chunk.get_records()
+        .map(Result::unwrap)
+        .filter(|record| record.poi_type == "minecraft:nether_portal")
+        .map(|record| record.position)
+        .collect();
+    assert_eq!(portals, [[17, -40, 5], [17, -39, 5]]);
+}
+
+#[test]
+fn terrain_chunks_are_not_poi() {
+    let mut reader = RegionFileReader::create(
+        Cursor::new(&include_bytes!("data/superflat-colored.mca")[..])).unwrap();
+    assert!(matches!(reader.get_poi_chunk(0, 31), Err(ChunkLoadError::MalformedChunk(_))));
+    assert!(matches!(poi_region().get_chunk(1, 0), Err(ChunkLoadError::MalformedChunk(_))));
+}
@@ -0,0 +1,89 @@
+use std::io::Cursor;
+
+use crab_nbt::{Nbt, NbtTag};
+use rusty_anvil::{RegionFile, RegionFileReader, RegionFileWriter, SliceRegionReader};
+use rusty_anvil::chunks::{Chunk, CompressionFormat};
+use rusty_anvil::container::RawChunk;
+use rusty_anvil::error::ChunkLoadError;
+use rusty_anvil::external::MemoryResolver;
+
+const REGION: &[u8] = include_bytes!("data/superflat-colored.mca");
+
+#[test]
+fn copy_region() {
+    let mut reader = RegionFileReader::create(Cursor::new(REGION)).unwrap();
+    // The writer's compression must not matter for raw chunks
+    let mut writer = RegionFileWriter::create(Cursor::new(Vec::new()), CompressionFormat::Lz4).unwrap();
+    for z in 0..32 {
+        for x in 0..32 {
+            match reader.get_raw_chunk(x, z) {
+                Err(ChunkLoadError::ChunkDoesNotExist) => continue,
+                result => writer.write_raw_chunk(x, z, &result.unwrap(), reader.get_timestamp(x, z).unwrap()).unwrap()
+            }
+        }
+    }
+    let copy = writer.finish().unwrap().into_inner();
+
+    let copy = SliceRegionReader::create(copy).unwrap();
+    assert_eq!(copy.get_timestamps().as_ref(), reader.get_timestamps().as_ref());
+    let original = reader.get_raw_chunk(0, 31).unwrap();
+    assert_eq!(original.get_compression_format(), Some(CompressionFormat::Zlib));
+    assert_eq!(copy.get_raw_chunk(0, 31).unwrap(), original);
+    assert_eq!(copy.get_chunk(0, 31).unwrap().data, reader.get_chunk(0, 31).unwrap().data);
+    assert_eq!(original.decode::<Chunk>().unwrap().data, reader.get_chunk(0, 31).unwrap().data);
+}
+
+#[test]
+fn replace_in_region_file() {
+    let mut reader = RegionFileReader::create(Cursor::new(REGION)).unwrap();
+    let raw = reader.get_raw_chunk(0, 31).unwrap();
+
+    let mut region = RegionFile::open(Cursor::new(REGION.to_vec()), CompressionFormat::Gzip).unwrap();
+    region.write_raw_chunk(5, 5, &raw, 7).unwrap();
+    assert_eq!(region.get_raw_chunk(5, 5).unwrap(), raw);
+    assert_eq!(region.get_timestamp(5, 5), Some(7));
+}
+
+#[test]
+fn unknown_compression() {
+    let raw = RawChunk { compression: 127, data: vec![1, 2, 3] };
+    let mut writer = RegionFileWriter::create(Cursor::new(Vec::new()), CompressionFormat::Zlib).unwrap();
+    writer.write_raw_chunk(0, 0, &raw, 1).unwrap();
+    let mut reader = RegionFileReader::create(Cursor::new(writer.finish().unwrap().into_inner())).unwrap();
+
+    let copy = reader.get_raw_chunk(0, 0).unwrap();
+    assert_eq!(copy, raw);
+    assert_eq!(copy.get_compression_format(), None);
+    assert!(matches!(copy.decode::<Nbt>(), Err(ChunkLoadError::UnknownCompressionFormat(127))));
+    assert!(matches!(reader.get_chunk(0, 0), Err(ChunkLoadError::UnknownCompressionFormat(127))));
+}
+
+#[test]
+fn external_flag_is_rejected() {
+    let raw = RawChunk { compression: 0x82, data: vec![1, 2, 3] };
+    let mut writer = RegionFileWriter::create(Cursor::new(Vec::new()), CompressionFormat::Zlib).unwrap();
+    assert!(writer.write_raw_chunk(0, 0, &raw, 1).is_err());
+}
+
+#[test]
+fn external_chunks() {
+    let mut reader = RegionFileReader::create(Cursor::new(REGION)).unwrap();
+    let mut chunk = reader.get_chunk(0, 31).unwrap();
+    chunk.data.root_tag.put("Padding".to_owned(), NbtTag::from(&vec![1u8; 1_500_000][..]));
+    let resolver = MemoryResolver::new();
+    let mut writer = RegionFileWriter::create(Cursor::new(Vec::new()), CompressionFormat::Uncompressed)
+        .unwrap()
+        .with_external_resolver(resolver.clone());
+    writer.write_chunk(1, 1, &chunk, 1).unwrap();
+    let mut reader = RegionFileReader::create(Cursor::new(writer.finish().unwrap().into_inner()))
+        .unwrap()
+        .with_external_resolver(resolver);
+
+    let raw = reader.get_raw_chunk(1, 1).unwrap();
+    assert_eq!(raw.compression, CompressionFormat::Uncompressed as u8);
+    assert_eq!(raw.data, chunk.data.write());
+
+    // Without a resolver the copy can't be stored
+    let mut writer = RegionFileWriter::create(Cursor::new(Vec::new()), CompressionFormat::Zlib).unwrap();
+    assert!(writer.write_raw_chunk(1, 1, &raw, 1).is_err());
+}
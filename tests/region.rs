@@ -0,0 +1,255 @@
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+
+use rusty_anvil::{RegionFile, RegionFileReader, SetLen};
+use rusty_anvil::chunks::CompressionFormat;
+
+fn open_copy() -> RegionFile<Cursor<Vec<u8>>> {
+    RegionFile::open(
+        Cursor::new(include_bytes!("data/superflat-colored.mca").to_vec()),
+        CompressionFormat::Zlib
+    ).unwrap()
+}
+
+#[test]
+fn replace_chunk() {
+    let mut region = open_copy();
+    let donor = region.get_chunk(0, 31).unwrap();
+    let original = region.get_chunk(5, 31).unwrap();
+    region.write_chunk(5, 31, &donor, 42).unwrap();
+
+    let file = region.into_inner().unwrap().into_inner();
+    assert_eq!(file.len(), include_bytes!("data/superflat-colored.mca").len(), "Region file should not grow");
+
+    let mut reader = RegionFileReader::create(Cursor::new(&file[..])).unwrap();
+    assert_eq!(reader.get_chunk(5, 31).unwrap().data, donor.data);
+    assert_ne!(reader.get_chunk(5, 31).unwrap().data, original.data);
+    assert_eq!(reader.get_timestamp(5, 31), Some(42));
+    assert_eq!(reader.get_chunk(0, 31).unwrap().data, donor.data);
+}
+
+#[test]
+fn grow_chunk() {
+    let mut region = open_copy();
+    // Uncompressed chunks are much larger and will not fit the existing sectors
+    region.set_compression(CompressionFormat::Uncompressed);
+    let chunk = region.get_chunk(3, 31).unwrap();
+    region.write_chunk(3, 31, &chunk, 7).unwrap();
+
+    let file = region.into_inner().unwrap().into_inner();
+    assert_eq!(file.len() % 4096, 0, "Region file is not sector aligned");
+    let mut reader = RegionFileReader::create(Cursor::new(&file[..])).unwrap();
+    for ([x, z], other) in reader.get_chunks() {
+        other.map(|other| other.unwrap_or_else(|e| panic!("{x},{z} failed to load: {e}")));
+    }
+    assert_eq!(reader.get_chunk(3, 31).unwrap().data, chunk.data);
+}
+
+#[test]
+fn delete_chunk() {
+    let mut region = open_copy();
+    region.delete_chunk(0, 31).unwrap();
+    assert!(region.get_chunk(0, 31).is_err());
+
+    let file = region.into_inner().unwrap().into_inner();
+    let reader = RegionFileReader::create(Cursor::new(&file[..])).unwrap();
+    assert_eq!(reader.get_timestamp(0, 31), Some(0));
+}
+
+#[test]
+fn compact() {
+    let mut region = open_copy();
+    let original_length = include_bytes!("data/superflat-colored.mca").len() as u64;
+    region.delete_chunk(0, 31).unwrap();
+    let reclaimed = region.compact().unwrap();
+    assert!(reclaimed > 0, "Deleting a chunk should leave unused sectors behind");
+
+    let file = region.into_inner().unwrap().into_inner();
+    assert_eq!(file.len() as u64, original_length - reclaimed);
+    assert_eq!(file.len() % 4096, 0, "Region file is not sector aligned");
+
+    let mut original = RegionFileReader::create(
+        Cursor::new(&include_bytes!("data/superflat-colored.mca")[..])).unwrap();
+    let mut compacted = RegionFileReader::create(Cursor::new(&file[..])).unwrap();
+    for ([x, z], chunk) in original.get_chunks() {
+        match chunk {
+            Some(chunk) if [x, z] != [0, 31] => assert_eq!(
+                chunk.unwrap().data, compacted.get_chunk(x, z).unwrap().data,
+                "{x},{z} does not match"
+            ),
+            _ => assert!(compacted.get_chunk(x, z).is_err(), "{x},{z} should not exist")
+        }
+    }
+}
+
+/// Creates a region file with the location entries and empty sectors up to the end of the last entry.
+fn with_entries(entries: &[(usize, u32, u8)]) -> Vec<u8> {
+    let end = entries.iter().map(|(_, offset, sectors)| offset + *sectors as u32).max().unwrap_or(2);
+    let mut file = vec![0u8; end as usize * 4096];
+    for (index, offset, sectors) in entries {
+        file[index * 4..index * 4 + 3].copy_from_slice(&offset.to_be_bytes()[1..]);
+        file[index * 4 + 3] = *sectors;
+    }
+    file
+}
+
+#[test]
+fn compact_overlapping() {
+    let file = with_entries(&[(0, 2, 4), (1, 4, 2), (2, 6, 1)]);
+    let mut region = RegionFile::open(Cursor::new(file.clone()), CompressionFormat::Zlib).unwrap();
+    let error = region.compact().unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    assert_eq!(region.into_inner().unwrap().into_inner(), file, "File should not be modified");
+}
+
+#[test]
+fn compact_truncated() {
+    let mut file = include_bytes!("data/superflat-colored.mca").to_vec();
+    file.truncate(file.len() - 4096);
+    let mut region = RegionFile::open(Cursor::new(file.clone()), CompressionFormat::Zlib).unwrap();
+    let error = region.compact().unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    assert_eq!(region.into_inner().unwrap().into_inner(), file, "File should not be modified");
+}
+
+#[test]
+fn out_of_bounds() {
+    let mut region = open_copy();
+    let chunk = region.get_chunk(0, 31).unwrap();
+    // (32, 0) would otherwise alias the slot of (0, 1)
+    assert_eq!(region.delete_chunk(0, 32).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+    assert_eq!(region.delete_chunk(32, 30).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+    assert_eq!(region.set_timestamp(32, 30, 5).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+    assert_eq!(region.write_chunk(32, 30, &chunk, 5).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+
+    let file = region.into_inner().unwrap().into_inner();
+    assert_eq!(file, include_bytes!("data/superflat-colored.mca"), "File should not be modified");
+}
+
+/// Opens a copy of the test region after letting `damage` modify its bytes.
+fn open_damaged(damage: impl FnOnce(&mut Vec<u8>)) -> RegionFile<Cursor<Vec<u8>>> {
+    let mut file = include_bytes!("data/superflat-colored.mca").to_vec();
+    damage(&mut file);
+    RegionFile::open(Cursor::new(file), CompressionFormat::Zlib).unwrap()
+}
+
+/// Checks that all chunks except the listed ones still match the original region.
+fn assert_unchanged(file: &[u8], except: &[[u8; 2]]) {
+    let mut original = RegionFileReader::create(
+        Cursor::new(&include_bytes!("data/superflat-colored.mca")[..])).unwrap();
+    let timestamps = original.get_timestamps().as_ref().to_vec();
+    let mut reader = RegionFileReader::create(Cursor::new(file)).unwrap();
+    for ([x, z], chunk) in original.get_chunks() {
+        if let Some(chunk) = chunk.filter(|_| !except.contains(&[x, z])) {
+            assert_eq!(chunk.unwrap().data, reader.get_chunk(x, z).unwrap().data, "{x},{z} does not match");
+            assert_eq!(timestamps[z as usize * 32 + x as usize], reader.get_timestamp(x, z).unwrap(), "{x},{z} timestamp does not match");
+        }
+    }
+}
+
+#[test]
+fn write_over_header() {
+    let entry = 31 * 32 * 4;
+    let mut region = open_damaged(|file| file[entry..entry + 3].copy_from_slice(&[0, 0, 1]));
+    let chunk = region.get_chunk(1, 6).unwrap();
+    region.write_chunk(0, 31, &chunk, 42).unwrap();
+
+    let file = region.into_inner().unwrap().into_inner();
+    assert_unchanged(&file, &[[0, 31]]);
+    let mut reader = RegionFileReader::create(Cursor::new(&file[..])).unwrap();
+    assert!(reader.get_location_table().get(0, 31).unwrap().get_sector_offset() >= 2);
+    assert_eq!(reader.get_chunk(0, 31).unwrap().data, chunk.data);
+}
+
+#[test]
+fn write_over_shared_sectors() {
+    let (first, second) = (31 * 32 * 4, (31 * 32 + 1) * 4);
+    let mut region = open_damaged(|file| {
+        let entry: [u8; 4] = file[first..first + 4].try_into().unwrap();
+        file[second..second + 4].copy_from_slice(&entry);
+    });
+    let chunk = region.get_chunk(2, 31).unwrap();
+    region.write_chunk(1, 31, &chunk, 42).unwrap();
+
+    let file = region.into_inner().unwrap().into_inner();
+    assert_unchanged(&file, &[[1, 31]]);
+    let mut reader = RegionFileReader::create(Cursor::new(&file[..])).unwrap();
+    assert_eq!(reader.get_chunk(1, 31).unwrap().data, chunk.data);
+}
+
+#[test]
+fn replace_entry_past_end_of_file() {
+    let entry = 31 * 32 * 4;
+    let damage = |file: &mut Vec<u8>| file[entry..entry + 3].copy_from_slice(&[0xff, 0xff, 0x00]);
+
+    let mut region = open_damaged(damage);
+    region.delete_chunk(0, 31).unwrap();
+    assert_unchanged(&region.into_inner().unwrap().into_inner(), &[[0, 31]]);
+
+    let mut region = open_damaged(damage);
+    let chunk = region.get_chunk(1, 31).unwrap();
+    region.write_chunk(0, 31, &chunk, 42).unwrap();
+    let file = region.into_inner().unwrap().into_inner();
+    assert_unchanged(&file, &[[0, 31]]);
+    assert_eq!(RegionFileReader::create(Cursor::new(&file[..])).unwrap().get_chunk(0, 31).unwrap().data, chunk.data);
+}
+
+/// A file that fails all writes after a number of successful ones, like a crash while writing.
+struct Interrupted {
+    file: Cursor<Vec<u8>>,
+    writes_left: usize
+}
+impl Read for Interrupted {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.file.read(buf)
+    }
+}
+impl Write for Interrupted {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.writes_left == 0 {
+            return Err(std::io::Error::other("Interrupted"));
+        }
+        self.writes_left -= 1;
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+impl Seek for Interrupted {
+    fn seek(&mut self, position: SeekFrom) -> std::io::Result<u64> {
+        self.file.seek(position)
+    }
+}
+impl SetLen for Interrupted {
+    fn set_len(&mut self, len: u64) -> std::io::Result<()> {
+        self.file.set_len(len)
+    }
+}
+
+#[test]
+fn compact_onto_own_sectors() {
+    let mut region = open_copy();
+    let small = region.get_chunk(0, 31).unwrap();
+    let large = region.get_chunk(1, 31).unwrap();
+    let mut file = RegionFile::create(Cursor::new(Vec::new()), CompressionFormat::Zlib).unwrap();
+    file.write_chunk(0, 0, &small, 1).unwrap();
+    // Uncompressed chunks span more sectors than the deleted chunk frees up
+    file.set_compression(CompressionFormat::Uncompressed);
+    file.write_chunk(1, 0, &large, 2).unwrap();
+    file.delete_chunk(0, 0).unwrap();
+    let file = file.into_inner().unwrap().into_inner();
+
+    for writes in 0.. {
+        let mut region = RegionFile::open(
+            Interrupted { file: Cursor::new(file.clone()), writes_left: writes }, CompressionFormat::Zlib
+        ).unwrap();
+        let result = region.compact();
+        let mut reader = RegionFileReader::create(region.into_inner().unwrap().file).unwrap();
+        assert_eq!(reader.get_chunk(1, 0).unwrap().data, large.data, "Chunk is corrupted after {writes} writes");
+        if result.is_ok() {
+            assert_eq!(reader.get_location_table().get(1, 0).unwrap().get_sector_offset(), 2);
+            break;
+        }
+    }
+}
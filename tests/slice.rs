@@ -0,0 +1,61 @@
+use std::io::Cursor;
+
+use rusty_anvil::{RegionFileReader, SliceRegionReader};
+
+#[test]
+fn matches_reader() {
+    let data = include_bytes!("data/superflat-colored.mca");
+    let slice = SliceRegionReader::create(&data[..]).unwrap();
+    let mut reader = RegionFileReader::create(Cursor::new(&data[..])).unwrap();
+
+    for (([x, z], expected), (coordinates, chunk)) in reader.get_chunks().zip(slice.get_chunks()) {
+        assert_eq!([x, z], coordinates);
+        assert_eq!(slice.get_timestamp(x, z) == Some(0), chunk.is_none());
+        assert_eq!(expected.is_some(), chunk.is_some(), "{x},{z} existence does not match");
+        if let (Some(expected), Some(chunk)) = (expected, chunk) {
+            assert_eq!(expected.unwrap().data, chunk.unwrap().data, "{x},{z} does not match");
+        }
+    }
+}
+
+#[test]
+fn shared_between_threads() {
+    let slice = SliceRegionReader::create(include_bytes!("data/superflat-colored.mca").to_vec()).unwrap();
+    std::thread::scope(|scope| {
+        for z in [0, 6, 31] {
+            let slice = &slice;
+            scope.spawn(move || {
+                for x in 0..32 {
+                    if slice.get_timestamp(x, z) != Some(0) {
+                        slice.get_chunk(x, z).unwrap();
+                    }
+                }
+            });
+        }
+    });
+}
+
+#[test]
+fn truncated_file() {
+    let data = include_bytes!("data/superflat-colored.mca");
+    let slice = SliceRegionReader::create(&data[..data.len() / 2]).unwrap();
+    let failures = slice.get_chunks()
+        .filter(|(_, chunk)| matches!(chunk, Some(Err(_))))
+        .count();
+    assert!(failures > 0, "Chunks beyond the end of the data should fail to load");
+}
+
+#[cfg(feature = "memmap")]
+#[test]
+fn memory_mapped() {
+    let path = std::env::temp_dir().join(format!("rusty-anvil-mmap-{}.mca", std::process::id()));
+    std::fs::write(&path, include_bytes!("data/superflat-colored.mca")).unwrap();
+    {
+        // SAFETY: The file is not modified by anyone else
+        let slice = unsafe { SliceRegionReader::open_mmap(&path) }.unwrap();
+        let mut reader = RegionFileReader::create(
+            Cursor::new(&include_bytes!("data/superflat-colored.mca")[..])).unwrap();
+        assert_eq!(slice.get_chunk(2, 31).unwrap().data, reader.get_chunk(2, 31).unwrap().data);
+    }
+    std::fs::remove_file(path).unwrap();
+}
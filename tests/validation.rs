@@ -0,0 +1,223 @@
+use std::io::Cursor;
+
+use rusty_anvil::{RegionFileReader, RegionFileWriter};
+use rusty_anvil::chunks::CompressionFormat;
+use rusty_anvil::validation::{ChunkIssue, RepairReport};
+
+fn region_bytes() -> Vec<u8> {
+    include_bytes!("data/superflat-colored.mca").to_vec()
+}
+
+fn offset_of(data: &[u8], x: usize, z: usize) -> usize {
+    let entry = (z * 32 + x) * 4;
+    u32::from_be_bytes([0, data[entry], data[entry + 1], data[entry + 2]]) as usize * 4096
+}
+
+fn issues_of(data: Vec<u8>, x: u8, z: u8) -> Vec<ChunkIssue> {
+    let report = RegionFileReader::create(Cursor::new(data)).unwrap().validate().unwrap();
+    report.chunks.into_iter()
+        .find(|chunk| chunk.coordinates == [x, z])
+        .expect("Chunk should have a report")
+        .issues
+}
+
+#[test]
+fn valid_region() {
+    let report = RegionFileReader::create(Cursor::new(region_bytes())).unwrap().validate().unwrap();
+    assert_eq!(report.chunks.len(), 702);
+    assert_eq!(report.file_length, region_bytes().len() as u64);
+    assert!(report.is_ok(), "{:?}", report.problems().next());
+}
+
+#[test]
+fn unknown_compression() {
+    let mut data = region_bytes();
+    let offset = offset_of(&data, 0, 31);
+    data[offset + 4] = 9;
+    let issues = issues_of(data, 0, 31);
+    assert!(matches!(issues[..], [ChunkIssue::UnknownCompression(9)]), "{issues:?}");
+}
+
+#[test]
+fn past_end_of_file() {
+    let mut data = region_bytes();
+    let entry = 31 * 32 * 4;
+    data[entry..entry + 3].copy_from_slice(&[0xff, 0xff, 0x00]);
+    let issues = issues_of(data, 0, 31);
+    assert!(matches!(issues[..], [ChunkIssue::PastEndOfFile]), "{issues:?}");
+}
+
+#[test]
+fn overlapping_chunks() {
+    let mut data = region_bytes();
+    let (first, second) = (31 * 32 * 4, (31 * 32 + 1) * 4);
+    let entry: [u8; 4] = data[first..first + 4].try_into().unwrap();
+    data[second..second + 4].copy_from_slice(&entry);
+
+    let report = RegionFileReader::create(Cursor::new(data)).unwrap().validate().unwrap();
+    let problems: Vec<_> = report.problems().collect();
+    assert_eq!(problems.len(), 2);
+    assert!(matches!(problems[0].issues[..], [ChunkIssue::Overlaps([1, 31])]), "{:?}", problems[0]);
+    assert!(matches!(problems[1].issues[..], [ChunkIssue::Overlaps([0, 31]), ChunkIssue::Misplaced([0, -1])]), "{:?}", problems[1]);
+}
+
+#[test]
+fn corrupt_payload() {
+    let mut data = region_bytes();
+    let offset = offset_of(&data, 0, 31);
+    let length = u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap()) as usize;
+    // Keep the zlib header intact and scramble the deflate stream
+    for byte in &mut data[offset + 7..offset + 4 + length] {
+        *byte ^= 0x5a;
+    }
+    let issues = issues_of(data, 0, 31);
+    assert!(matches!(issues[..], [ChunkIssue::Decompression(_)]), "{issues:?}");
+}
+
+#[test]
+fn length_exceeds_sectors() {
+    let mut data = region_bytes();
+    let offset = offset_of(&data, 0, 31);
+    data[offset..offset + 4].copy_from_slice(&0x10_0000u32.to_be_bytes());
+    let issues = issues_of(data, 0, 31);
+    assert!(matches!(issues[..], [ChunkIssue::LengthExceedsSectors { length: 0x10_0000, .. }]), "{issues:?}");
+}
+
+#[test]
+fn truncated_nbt() {
+    let mut reader = RegionFileReader::create(Cursor::new(region_bytes())).unwrap();
+    let chunk = reader.get_chunk(0, 31).unwrap();
+    // Short enough to fit into the chunk's sectors uncompressed
+    let nbt = &chunk.data.write()[..200];
+
+    let mut data = region_bytes();
+    let offset = offset_of(&data, 0, 31);
+    let mut payload = ((nbt.len() + 1) as u32).to_be_bytes().to_vec();
+    payload.push(3);
+    payload.extend_from_slice(nbt);
+    data[offset..offset + payload.len()].copy_from_slice(&payload);
+
+    let issues = issues_of(data, 0, 31);
+    assert!(matches!(issues[..], [ChunkIssue::MalformedNbt(_)]), "{issues:?}");
+}
+
+fn repair(data: Vec<u8>, scan_sectors: bool) -> (RepairReport, Vec<u8>) {
+    let mut writer = RegionFileWriter::create(Cursor::new(Vec::new()), CompressionFormat::Zlib).unwrap();
+    let report = RegionFileReader::create(Cursor::new(data)).unwrap()
+        .repair(&mut writer, scan_sectors).unwrap();
+    (report, writer.finish().unwrap().into_inner())
+}
+
+#[test]
+fn repair_valid_region() {
+    let (report, repaired) = repair(region_bytes(), false);
+    assert_eq!(report.salvaged.len(), 702);
+    assert!(report.fixed.is_empty() && report.recovered.is_empty() && report.dropped.is_empty(), "{report:?}");
+
+    let mut original = RegionFileReader::create(Cursor::new(region_bytes())).unwrap();
+    let mut repaired = RegionFileReader::create(Cursor::new(repaired)).unwrap();
+    assert!(repaired.validate().unwrap().is_ok());
+    for [x, z] in report.salvaged {
+        assert_eq!(repaired.get_chunk(x, z).unwrap().data, original.get_chunk(x, z).unwrap().data);
+        assert_eq!(repaired.get_timestamp(x, z), original.get_timestamp(x, z));
+    }
+}
+
+#[test]
+fn repair_drops_corrupt_chunks() {
+    let mut data = region_bytes();
+    let offset = offset_of(&data, 0, 31);
+    data[offset + 4] = 9;
+    let (report, repaired) = repair(data, false);
+    assert_eq!(report.salvaged.len(), 701);
+    assert!(matches!(report.dropped[..], [([0, 31], ChunkIssue::UnknownCompression(9))]), "{report:?}");
+
+    let mut repaired = RegionFileReader::create(Cursor::new(repaired)).unwrap();
+    assert!(repaired.get_location_table().get(0, 31).unwrap().is_empty());
+    assert!(repaired.validate().unwrap().is_ok());
+}
+
+#[test]
+fn repair_sector_count() {
+    let mut data = region_bytes();
+    // Claim more sectors than the chunk uses
+    data[31 * 32 * 4 + 3] = 200;
+    assert!(!RegionFileReader::create(Cursor::new(data.clone())).unwrap().validate().unwrap().is_ok());
+
+    let (report, repaired) = repair(data, false);
+    assert_eq!(report.fixed, vec![[0, 31]]);
+    assert_eq!(report.salvaged.len(), 702);
+    assert!(RegionFileReader::create(Cursor::new(repaired)).unwrap().validate().unwrap().is_ok());
+}
+
+#[test]
+fn repair_lost_entry() {
+    let mut data = region_bytes();
+    let entry = (31 * 32 + 3) * 4;
+    data[entry..entry + 4].fill(0);
+    data[4096 + entry..4096 + entry + 4].fill(0);
+
+    let (report, _) = repair(data.clone(), false);
+    assert_eq!(report.salvaged.len(), 701);
+    assert!(report.recovered.is_empty());
+
+    let (report, repaired) = repair(data, true);
+    assert_eq!(report.salvaged.len(), 701);
+    assert_eq!(report.recovered, vec![[3, 31]]);
+    let mut repaired = RegionFileReader::create(Cursor::new(repaired)).unwrap();
+    let mut original = RegionFileReader::create(Cursor::new(region_bytes())).unwrap();
+    assert_eq!(repaired.get_chunk(3, 31).unwrap().data, original.get_chunk(3, 31).unwrap().data);
+    assert_ne!(repaired.get_timestamp(3, 31), Some(0));
+}
+
+#[test]
+fn overlaps_header() {
+    for offset in [0u8, 1] {
+        let mut data = region_bytes();
+        let entry = 31 * 32 * 4;
+        data[entry..entry + 4].copy_from_slice(&[0, 0, offset, 1]);
+        let issues = issues_of(data.clone(), 0, 31);
+        assert!(matches!(issues[..], [ChunkIssue::OverlapsHeader, ..]), "{issues:?}");
+
+        let (report, _) = repair(data.clone(), false);
+        assert!(matches!(report.dropped[..], [([0, 31], ChunkIssue::OverlapsHeader)]), "{report:?}");
+
+        // The chunk's data is still in place and can be found by scanning
+        let (report, repaired) = repair(data, true);
+        assert!(report.dropped.is_empty(), "{report:?}");
+        assert_eq!(report.recovered, vec![[0, 31]]);
+        let mut repaired = RegionFileReader::create(Cursor::new(repaired)).unwrap();
+        assert!(repaired.validate().unwrap().is_ok());
+        let mut original = RegionFileReader::create(Cursor::new(region_bytes())).unwrap();
+        assert_eq!(repaired.get_chunk(0, 31).unwrap().data, original.get_chunk(0, 31).unwrap().data);
+    }
+}
+
+#[test]
+fn no_sectors() {
+    let mut data = region_bytes();
+    data[31 * 32 * 4 + 3] = 0;
+    let issues = issues_of(data.clone(), 0, 31);
+    assert!(matches!(issues[..], [ChunkIssue::NoSectors]), "{issues:?}");
+
+    // The length prefix still tells how many sectors the chunk uses
+    let (report, repaired) = repair(data, false);
+    assert_eq!(report.fixed, vec![[0, 31]]);
+    assert_eq!(report.salvaged.len(), 702);
+    assert!(RegionFileReader::create(Cursor::new(repaired)).unwrap().validate().unwrap().is_ok());
+}
+
+#[test]
+fn zero_length() {
+    let mut data = region_bytes();
+    let offset = offset_of(&data, 0, 31);
+    data[offset..offset + 4].fill(0);
+    let issues = issues_of(data.clone(), 0, 31);
+    assert!(matches!(issues[..], [ChunkIssue::ZeroLength]), "{issues:?}");
+
+    let (report, repaired) = repair(data, false);
+    assert!(matches!(report.dropped[..], [([0, 31], ChunkIssue::ZeroLength)]), "{report:?}");
+    let mut repaired = RegionFileReader::create(Cursor::new(repaired)).unwrap();
+    assert!(repaired.get_location_table().get(0, 31).unwrap().is_empty());
+    assert!(repaired.validate().unwrap().is_ok());
+}
@@ -0,0 +1,88 @@
+use std::io::Cursor;
+use std::path::PathBuf;
+
+use rusty_anvil::RegionFileReader;
+use rusty_anvil::world::{split_chunk_coordinates, DimensionId, World};
+
+/// Creates a world directory containing the test region as r.0.-1.mca in the given dimensions
+fn create_world(name: &str, region_directories: &[&str]) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("rusty-anvil-{name}-{}", std::process::id()));
+    for directory in region_directories {
+        let directory = path.join(directory);
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("r.0.-1.mca"), include_bytes!("data/superflat-colored.mca")).unwrap();
+    }
+    path
+}
+
+#[test]
+fn discovers_dimensions() {
+    let path = create_world("dimensions", &["region", "DIM1/region", "dimensions/test/skylands/region"]);
+    let world = World::open(&path).unwrap();
+    let mut dimensions: Vec<_> = world.get_dimensions().cloned().collect();
+    dimensions.sort_by_key(|id| format!("{id:?}"));
+    assert_eq!(dimensions, vec![
+        DimensionId::Custom { namespace: "test".to_owned(), name: "skylands".to_owned() },
+        DimensionId::End,
+        DimensionId::Overworld,
+    ]);
+    std::fs::remove_dir_all(path).unwrap();
+}
+
+#[test]
+fn loads_chunks_by_global_coordinates() {
+    let path = create_world("chunks", &["region"]);
+    let mut world = World::open(&path).unwrap();
+    let mut reader = RegionFileReader::create(
+        Cursor::new(&include_bytes!("data/superflat-colored.mca")[..])).unwrap();
+
+    assert_eq!(world.get_chunk(3, -1).unwrap().data, reader.get_chunk(3, 31).unwrap().data);
+    assert_eq!(world.get_chunk(0, -26).unwrap().data, reader.get_chunk(0, 6).unwrap().data);
+    assert!(world.get_chunk(0, 0).is_err(), "Region r.0.0.mca does not exist");
+
+    let overworld = world.get_dimension(&DimensionId::Overworld).unwrap();
+    assert_eq!(overworld.list_regions().unwrap(), vec![[0, -1]]);
+    std::fs::remove_dir_all(path).unwrap();
+}
+
+#[test]
+fn chunk_coordinates() {
+    assert_eq!(split_chunk_coordinates(0, 0), ([0, 0], [0, 0]));
+    assert_eq!(split_chunk_coordinates(-1, 32), ([-1, 1], [31, 0]));
+    assert_eq!(split_chunk_coordinates(-33, 63), ([-2, 1], [31, 31]));
+}
+
+#[test]
+fn blocks_by_global_coordinates() {
+    let path = create_world("blocks", &["region"]);
+    let mut world = World::open(&path).unwrap();
+    let mut reader = RegionFileReader::create(
+        Cursor::new(&include_bytes!("data/superflat-colored.mca")[..])).unwrap();
+    let chunk = reader.get_chunk(1, 31).unwrap();
+
+    for y in -64..-58 {
+        assert_eq!(
+            world.get_block_at(17, y, -3).unwrap(),
+            chunk.get_block_at(17, y, -3).unwrap(),
+            "Block at 17,{y},-3 does not match"
+        );
+    }
+    assert!(world.get_block_at(0, -64, 0).unwrap().is_none(), "Region r.0.0.mca does not exist");
+    std::fs::remove_dir_all(path).unwrap();
+}
+
+#[test]
+fn close_region() {
+    let path = create_world("close", &["region"]);
+    let mut world = World::open(&path).unwrap();
+    let overworld = world.get_dimension(&DimensionId::Overworld).unwrap();
+    assert!(overworld.load_chunk(0, -1).unwrap().is_some());
+
+    // Chunks are served from memory until the region is closed
+    std::fs::remove_file(path.join("region/r.0.-1.mca")).unwrap();
+    assert!(overworld.load_chunk(0, -1).unwrap().is_some());
+    overworld.close_region(0, -1);
+    assert!(overworld.load_chunk(0, -1).unwrap().is_none());
+    assert!(overworld.get_region(0, -1).unwrap().is_none());
+    std::fs::remove_dir_all(path).unwrap();
+}
@@ -0,0 +1,75 @@
+use std::io::Cursor;
+
+use rusty_anvil::{RegionFileReader, RegionFileWriter};
+use rusty_anvil::chunks::{Chunk, CompressionFormat};
+use rusty_anvil::error::ChunkLoadError;
+
+fn round_trip(compression: CompressionFormat) {
+    let mut reader = RegionFileReader::create(
+        Cursor::new(&include_bytes!("data/superflat-colored.mca")[..])).unwrap();
+    let mut writer = RegionFileWriter::create(Cursor::new(Vec::new()), compression).unwrap();
+    for ([x, z], chunk) in reader.get_chunks() {
+        if let Some(chunk) = chunk {
+            writer.write_chunk(x, z, &chunk.unwrap(), 1234).unwrap();
+        }
+    }
+    let written = writer.finish().unwrap().into_inner();
+    assert_eq!(written.len() % 4096, 0, "Region file is not sector aligned");
+
+    let mut copy = RegionFileReader::create(Cursor::new(&written[..])).unwrap();
+    for ([x, z], chunk) in reader.get_chunks() {
+        match chunk {
+            None => assert!(copy.get_chunk(x, z).is_err(), "{x},{z} should not exist"),
+            Some(chunk) => {
+                assert_eq!(chunk.unwrap().data, copy.get_chunk(x, z).unwrap().data, "{x},{z} does not match");
+                assert_eq!(copy.get_timestamp(x, z), Some(1234));
+            }
+        }
+    }
+}
+
+#[test]
+fn round_trip_zlib() {
+    round_trip(CompressionFormat::Zlib);
+}
+
+#[test]
+fn round_trip_lz4() {
+    round_trip(CompressionFormat::Lz4);
+}
+
+#[test]
+fn round_trip_uncompressed() {
+    round_trip(CompressionFormat::Uncompressed);
+}
+
+#[test]
+fn chunk_payload_round_trip() {
+    let mut reader = RegionFileReader::create(
+        Cursor::new(&include_bytes!("data/superflat-colored.mca")[..])).unwrap();
+    let chunk = reader.get_chunk(0, 31).unwrap();
+    for compression in [CompressionFormat::Gzip, CompressionFormat::Zlib, CompressionFormat::Uncompressed, CompressionFormat::Lz4] {
+        let payload = chunk.write(compression).unwrap();
+        assert_eq!(u32::from_be_bytes(payload[..4].try_into().unwrap()) as usize, payload.len() - 4);
+        assert_eq!(payload[4], compression as u8);
+        assert_eq!(Chunk::read(&payload).unwrap().data, chunk.data);
+    }
+    let uncompressed = chunk.write(CompressionFormat::Uncompressed).unwrap();
+    assert_eq!(&uncompressed[5..], &chunk.data.write()[..]);
+}
+
+#[test]
+fn read_external_payload() {
+    // Only the compression byte with the external flag is stored in the region file
+    assert!(matches!(Chunk::read(&[0, 0, 0, 1, 0x82]), Err(ChunkLoadError::ExternalChunkUnavailable)));
+}
+
+#[test]
+fn write_out_of_bounds() {
+    let mut reader = RegionFileReader::create(
+        Cursor::new(&include_bytes!("data/superflat-colored.mca")[..])).unwrap();
+    let chunk = reader.get_chunk(0, 31).unwrap();
+    let mut writer = RegionFileWriter::create(Cursor::new(Vec::new()), CompressionFormat::Zlib).unwrap();
+    let error = writer.write_chunk(32, 0, &chunk, 0).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+}